@@ -1,37 +1,339 @@
-use lazy_static::lazy_static;
-use regex::Regex;
+use std::fmt;
 use std::time::Instant;
 use std::vec::Vec;
 use ya_advent_lib::read::read_input;
 
-lazy_static! {
-    static ref RE: Regex = Regex::new(r"mul\((\d{1,3}),(\d{1,3})\)").unwrap();
-    static ref DD_RE: Regex = Regex::new(r"don't\(\).*?do\(\)").unwrap();
+type OpFn = fn(&[i64]) -> Option<i64>;
+
+struct InstrDef {
+    name: String,
+    arity: usize,
+    max_digits: usize,
+    op: OpFn,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Instr {
+    Do,
+    Dont,
+    Op(usize, Vec<i64>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Token {
+    offset: usize,
+    len: usize,
+    instr: Instr,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Reject {
+    Expected(char, Option<char>),
+    NoOperand(Option<char>),
+    TooManyDigits(usize),
+    OutOfRange,
+    Overflow,
 }
 
-fn part1(input: &[String]) -> i64 {
-    input
-        .iter()
-        .map(|line| {
-            RE.captures_iter(line)
-                .map(|cap| {
-                    cap.get(1).unwrap().as_str().parse::<i64>().unwrap()
-                        * cap.get(2).unwrap().as_str().parse::<i64>().unwrap()
-                })
-                .sum::<i64>()
-        })
-        .sum()
+impl fmt::Display for Reject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let found = |c: &Option<char>| match c {
+            Some(c) => format!("'{c}'"),
+            None => "end of line".to_string(),
+        };
+        match self {
+            Reject::Expected(c, got) => write!(f, "expected '{c}', found {}", found(got)),
+            Reject::NoOperand(got) => write!(f, "expected operand, found {}", found(got)),
+            Reject::TooManyDigits(n) => write!(f, "operand longer than {n} digits"),
+            Reject::OutOfRange => write!(f, "operand out of range"),
+            Reject::Overflow => write!(f, "result out of range"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct NearMiss {
+    offset: usize,
+    len: usize,
+    reason: Reject,
 }
 
-fn part2(input: &[String]) -> i64 {
-    let joined = input.join(" ");
-    let line = DD_RE.replace_all(&joined, "X");
-    RE.captures_iter(&line)
-        .map(|cap| {
-            cap.get(1).unwrap().as_str().parse::<i64>().unwrap()
-                * cap.get(2).unwrap().as_str().parse::<i64>().unwrap()
-        })
-        .sum()
+fn found_at(mem: &[u8], pos: usize) -> Option<char> {
+    mem.get(pos).map(|b| *b as char)
+}
+
+enum Finding {
+    Accepted {
+        line: usize,
+        col: usize,
+        text: String,
+        enabled: bool,
+        value: Option<i64>,
+    },
+    NearMiss {
+        line: usize,
+        col: usize,
+        text: String,
+        reason: Reject,
+    },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::Accepted {
+                line,
+                col,
+                text,
+                enabled,
+                value,
+            } => {
+                write!(f, "{line}:{col} {text}")?;
+                if let Some(v) = value {
+                    write!(f, " = {v}")?;
+                    if !enabled {
+                        write!(f, " (disabled)")?;
+                    }
+                }
+                Ok(())
+            }
+            Finding::NearMiss {
+                line,
+                col,
+                text,
+                reason,
+            } => write!(f, "{line}:{col} {text} rejected: {reason}"),
+        }
+    }
+}
+
+struct Interpreter {
+    defs: Vec<InstrDef>,
+}
+
+impl Interpreter {
+    fn new() -> Self {
+        let mut interp = Self { defs: Vec::new() };
+        interp.register("mul", 2, 3, |args| args[0].checked_mul(args[1]));
+        interp
+    }
+
+    fn register(&mut self, name: &str, arity: usize, max_digits: usize, op: OpFn) {
+        self.defs.push(InstrDef {
+            name: name.to_string(),
+            arity,
+            max_digits,
+            op,
+        });
+    }
+
+    fn number_at(
+        mem: &[u8],
+        pos: usize,
+        max_digits: usize,
+    ) -> Result<(i64, usize), (Reject, usize)> {
+        let len = mem[pos..].iter().take_while(|b| b.is_ascii_digit()).count();
+        if len == 0 {
+            return Err((Reject::NoOperand(found_at(mem, pos)), pos + 1));
+        }
+        if len > max_digits {
+            return Err((Reject::TooManyDigits(max_digits), pos + len));
+        }
+        let n = std::str::from_utf8(&mem[pos..pos + len])
+            .unwrap()
+            .parse::<i64>()
+            .map_err(|_| (Reject::OutOfRange, pos + len))?;
+        Ok((n, pos + len))
+    }
+
+    fn expect(mem: &[u8], pos: usize, c: char) -> Result<usize, (Reject, usize)> {
+        if mem.get(pos) == Some(&(c as u8)) {
+            Ok(pos + 1)
+        } else {
+            Err((Reject::Expected(c, found_at(mem, pos)), pos + 1))
+        }
+    }
+
+    fn call_at(
+        name: &str,
+        arity: usize,
+        max_digits: usize,
+        mem: &[u8],
+        pos: usize,
+    ) -> Result<(Vec<i64>, usize), (Reject, usize)> {
+        let mut pos = Self::expect(mem, pos + name.len(), '(')?;
+        let mut args = Vec::with_capacity(arity);
+        for n in 0..arity {
+            if n > 0 {
+                pos = Self::expect(mem, pos, ',')?;
+            }
+            let (arg, next) = Self::number_at(mem, pos, max_digits)?;
+            args.push(arg);
+            pos = next;
+        }
+        pos = Self::expect(mem, pos, ')')?;
+        Ok((args, pos))
+    }
+
+    fn scan_at(&self, mem: &[u8], pos: usize) -> Option<Result<Token, NearMiss>> {
+        let rest = &mem[pos..];
+        let builtin = if rest.starts_with(b"don't") {
+            Some(("don't", Instr::Dont))
+        } else if rest.starts_with(b"do") {
+            Some(("do", Instr::Do))
+        } else {
+            None
+        };
+        let candidates = builtin
+            .into_iter()
+            .map(|(name, instr)| (name, 0, 0, instr))
+            .chain(
+                self.defs
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, def)| rest.starts_with(def.name.as_bytes()))
+                    .map(|(idx, def)| {
+                        (
+                            def.name.as_str(),
+                            def.arity,
+                            def.max_digits,
+                            Instr::Op(idx, vec![]),
+                        )
+                    }),
+            );
+        let mut near_miss = None;
+        for (name, arity, max_digits, instr) in candidates {
+            let called = Self::call_at(name, arity, max_digits, mem, pos).and_then(
+                |(args, end)| match instr {
+                    Instr::Op(idx, _) => match (self.defs[idx].op)(&args) {
+                        Some(_) => Ok((Instr::Op(idx, args), end)),
+                        None => Err((Reject::Overflow, end)),
+                    },
+                    other => Ok((other, end)),
+                },
+            );
+            match called {
+                Ok((instr, end)) => {
+                    return Some(Ok(Token {
+                        offset: pos,
+                        len: end - pos,
+                        instr,
+                    }));
+                }
+                Err((reason, end)) => {
+                    if near_miss.is_none() {
+                        near_miss = Some(NearMiss {
+                            offset: pos,
+                            len: end.min(mem.len()) - pos,
+                            reason,
+                        });
+                    }
+                }
+            }
+        }
+        near_miss.map(Err)
+    }
+
+    fn scan(&self, mem: &str) -> Vec<Result<Token, NearMiss>> {
+        let mem = mem.as_bytes();
+        let mut found = Vec::new();
+        let mut pos = 0;
+        while pos < mem.len() {
+            match self.scan_at(mem, pos) {
+                Some(Ok(token)) => {
+                    pos += token.len;
+                    found.push(Ok(token));
+                }
+                Some(Err(near_miss)) => {
+                    pos += 1;
+                    found.push(Err(near_miss));
+                }
+                None => {
+                    pos += 1;
+                }
+            }
+        }
+        found
+    }
+
+    fn lex(&self, mem: &str) -> Vec<Token> {
+        self.scan(mem).into_iter().flatten().collect()
+    }
+
+    fn run(&self, input: &[String], conditionals: bool) -> i128 {
+        let mut enabled = true;
+        let mut total = 0;
+        for line in input {
+            for token in self.lex(line) {
+                match token.instr {
+                    Instr::Do => {
+                        enabled = true;
+                    }
+                    Instr::Dont => {
+                        enabled = false;
+                    }
+                    Instr::Op(idx, args) => {
+                        if enabled || !conditionals {
+                            if let Some(v) = (self.defs[idx].op)(&args) {
+                                total += i128::from(v);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        total
+    }
+
+    fn report(&self, input: &[String]) -> Vec<Finding> {
+        let mut enabled = true;
+        let mut findings = Vec::new();
+        for (lineno, line) in input.iter().enumerate() {
+            for found in self.scan(line) {
+                let finding = match found {
+                    Ok(token) => {
+                        let value = match &token.instr {
+                            Instr::Do => {
+                                enabled = true;
+                                None
+                            }
+                            Instr::Dont => {
+                                enabled = false;
+                                None
+                            }
+                            Instr::Op(idx, args) => (self.defs[*idx].op)(args),
+                        };
+                        Finding::Accepted {
+                            line: lineno + 1,
+                            col: token.offset + 1,
+                            text: line[token.offset..token.offset + token.len].to_string(),
+                            enabled,
+                            value,
+                        }
+                    }
+                    Err(near_miss) => Finding::NearMiss {
+                        line: lineno + 1,
+                        col: near_miss.offset + 1,
+                        text: String::from_utf8_lossy(
+                            &line.as_bytes()[near_miss.offset..near_miss.offset + near_miss.len],
+                        )
+                        .to_string(),
+                        reason: near_miss.reason,
+                    },
+                };
+                findings.push(finding);
+            }
+        }
+        findings
+    }
+}
+
+fn part1(input: &[String]) -> i128 {
+    Interpreter::new().run(input, false)
+}
+
+fn part2(input: &[String]) -> i128 {
+    Interpreter::new().run(input, true)
 }
 
 fn main() {
@@ -44,6 +346,11 @@ fn main() {
     let part2 = part2(&input);
     let duration = start.elapsed();
     println!("Part 2: {part2} ({duration:?})");
+    if std::env::var_os("REPORT").is_some() {
+        for finding in Interpreter::new().report(&input) {
+            println!("{finding}");
+        }
+    }
 }
 
 #[cfg(test)]
@@ -60,4 +367,95 @@ mod tests {
             test_input("xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))");
         assert_eq!(part2(&input), 48);
     }
+
+    #[test]
+    fn day03_interpreter_test() {
+        let input: Vec<String> = test_input("mul(2,4)don't()\nmul(3,3)mul(1000,1)\nmul(5,5");
+        assert_eq!(part1(&input), 17);
+        assert_eq!(part2(&input), 8);
+
+        let mut interp = Interpreter::new();
+        interp.register("add", 3, 2, |args| {
+            args.iter().try_fold(0i64, |acc, n| acc.checked_add(*n))
+        });
+        interp.register("sub", 2, 4, |args| args[0].checked_sub(args[1]));
+        let tokens = interp.lex("add(1,2,3)sub(1000,1)mul(mul(2,2)don't()");
+        assert_eq!(
+            tokens.iter().map(|t| t.offset).collect::<Vec<_>>(),
+            vec![0, 10, 25, 33]
+        );
+        assert_eq!(tokens[3].instr, Instr::Dont);
+        let input: Vec<String> = test_input("add(1,2,3)sub(1000,1)add(100,1,1)don't()\nmul(2,2)");
+        assert_eq!(interp.run(&input, true), 1005);
+
+        interp.register("big", 1, 25, |args| Some(args[0]));
+        assert!(interp.lex("big(12345678901234567890)").is_empty());
+        assert_eq!(
+            interp.scan("big(12345678901234567890)"),
+            vec![Err(NearMiss {
+                offset: 0,
+                len: 24,
+                reason: Reject::OutOfRange,
+            })]
+        );
+        let input: Vec<String> = test_input("big(1234567890123456789)");
+        assert_eq!(interp.run(&input, false), 1234567890123456789);
+        let input: Vec<String> = test_input("big(9223372036854775807)big(9223372036854775807)");
+        assert_eq!(interp.run(&input, false), 2 * i64::MAX as i128);
+
+        interp.register("sq", 1, 19, |args| args[0].checked_mul(args[0]));
+        assert_eq!(
+            interp.scan("sq(4000000000)sq(3)"),
+            vec![
+                Err(NearMiss {
+                    offset: 0,
+                    len: 14,
+                    reason: Reject::Overflow,
+                }),
+                Ok(Token {
+                    offset: 14,
+                    len: 5,
+                    instr: Instr::Op(4, vec![3]),
+                }),
+            ]
+        );
+        let input: Vec<String> = test_input("sq(4000000000)sq(3)");
+        assert_eq!(interp.run(&input, false), 9);
+        assert_eq!(
+            interp
+                .report(&input)
+                .iter()
+                .map(|f| f.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "1:1 sq(4000000000) rejected: result out of range",
+                "1:15 sq(3) = 9",
+            ]
+        );
+    }
+
+    #[test]
+    fn day03_report_test() {
+        let input: Vec<String> =
+            test_input("xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))\nmul(1234,5)");
+        let report = Interpreter::new()
+            .report(&input)
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            report,
+            vec![
+                "1:2 mul(2,4) = 8",
+                "1:11 mul[ rejected: expected '(', found '['",
+                "1:21 don't()",
+                "1:29 mul(5,5) = 25 (disabled)",
+                "1:38 mul(32,64] rejected: expected ')', found ']'",
+                "1:49 mul(11,8) = 88 (disabled)",
+                "1:60 do()",
+                "1:65 mul(8,5) = 40",
+                "2:1 mul(1234 rejected: operand longer than 3 digits",
+            ]
+        );
+    }
 }
@@ -1,4 +1,5 @@
 use itertools::Itertools;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::time::Instant;
 use std::vec::Vec;
 use ya_advent_lib::coords::Coord2D;
@@ -9,53 +10,302 @@ fn mkgrid(inp: &[String]) -> Grid<char> {
     Grid::from_input(inp, '.', 1)
 }
 
-const WORD: [char; 4] = ['X', 'M', 'A', 'S'];
+#[derive(Clone, Debug, PartialEq)]
+struct Hit {
+    word: String,
+    start: Coord2D,
+    dir: Coord2D,
+}
 
-fn num_words_at(x: i64, y: i64, grid: &Grid<char>) -> usize {
-    let center = Coord2D::new(x, y);
-    Coord2D::new(0, 0)
-        .neighbors8()
-        .iter()
-        .filter(|c| {
-            WORD.iter().enumerate().all(|(n, letter)| {
-                let loc = center + **c * (n as i64);
-                grid.get_c(loc) == *letter
-            })
-        })
-        .count()
+impl Hit {
+    fn cells(&self) -> impl Iterator<Item = Coord2D> + '_ {
+        (0..self.word.chars().count() as i64).map(|n| self.start + self.dir * n)
+    }
+
+    fn dir_name(&self) -> &'static str {
+        match (self.dir.x.signum(), self.dir.y.signum()) {
+            (0, -1) => "N",
+            (1, -1) => "NE",
+            (1, 0) => "E",
+            (1, 1) => "SE",
+            (0, 1) => "S",
+            (-1, 1) => "SW",
+            (-1, 0) => "W",
+            (-1, -1) => "NW",
+            _ => "?",
+        }
+    }
+}
+
+#[derive(Default)]
+struct AcNode {
+    next: HashMap<char, usize>,
+    fail: usize,
+    out: Vec<(usize, bool)>,
+}
+
+struct WordSearch {
+    words: Vec<Vec<char>>,
+    nodes: Vec<AcNode>,
+}
+
+impl WordSearch {
+    fn new(words: &[&str]) -> Self {
+        let words: Vec<Vec<char>> = words.iter().map(|w| w.chars().collect()).collect();
+        let mut nodes = vec![AcNode::default()];
+        for (idx, word) in words.iter().enumerate() {
+            for (reversed, pattern) in [
+                (false, word.clone()),
+                (true, word.iter().rev().copied().collect()),
+            ] {
+                let mut node = 0;
+                for c in pattern {
+                    node = match nodes[node].next.get(&c) {
+                        Some(n) => *n,
+                        None => {
+                            nodes.push(AcNode::default());
+                            let n = nodes.len() - 1;
+                            nodes[node].next.insert(c, n);
+                            n
+                        }
+                    };
+                }
+                nodes[node].out.push((idx, reversed));
+            }
+        }
+        let mut queue: VecDeque<usize> = nodes[0].next.values().copied().collect();
+        while let Some(node) = queue.pop_front() {
+            let edges: Vec<(char, usize)> =
+                nodes[node].next.iter().map(|(c, n)| (*c, *n)).collect();
+            for (c, child) in edges {
+                let mut f = nodes[node].fail;
+                while f != 0 && !nodes[f].next.contains_key(&c) {
+                    f = nodes[f].fail;
+                }
+                let fail = nodes[f].next.get(&c).copied().unwrap_or(0);
+                nodes[child].fail = fail;
+                let inherited = nodes[fail].out.clone();
+                nodes[child].out.extend(inherited);
+                queue.push_back(child);
+            }
+        }
+        Self { words, nodes }
+    }
+
+    fn step(&self, mut node: usize, c: char) -> usize {
+        loop {
+            if let Some(n) = self.nodes[node].next.get(&c) {
+                return *n;
+            }
+            if node == 0 {
+                return 0;
+            }
+            node = self.nodes[node].fail;
+        }
+    }
+
+    fn lines(grid: &Grid<char>, dir: Coord2D, wrap: bool) -> Vec<Vec<Coord2D>> {
+        let xb = grid.x_bounds_orig();
+        let yb = grid.y_bounds_orig();
+        let inside = |c: Coord2D| xb.contains(&c.x) && yb.contains(&c.y);
+        let advance = |c: Coord2D| {
+            let n = c + dir;
+            if wrap {
+                Coord2D::new(
+                    (n.x - xb.start).rem_euclid(xb.end - xb.start) + xb.start,
+                    (n.y - yb.start).rem_euclid(yb.end - yb.start) + yb.start,
+                )
+            } else {
+                n
+            }
+        };
+        let mut seen: HashSet<Coord2D> = HashSet::new();
+        let mut lines = Vec::new();
+        for (y, x) in yb.clone().cartesian_product(xb.clone()) {
+            let start = Coord2D::new(x, y);
+            if seen.contains(&start) || (!wrap && inside(start - dir)) {
+                continue;
+            }
+            let mut line = Vec::new();
+            let mut pos = start;
+            while inside(pos) && !seen.contains(&pos) {
+                seen.insert(pos);
+                line.push(pos);
+                pos = advance(pos);
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    fn find_all(&self, grid: &Grid<char>, wrap: bool) -> Vec<Hit> {
+        let maxlen = self.words.iter().map(|w| w.len()).max().unwrap_or(0);
+        let mut hits = Vec::new();
+        for dir in [(1, 0), (0, 1), (1, 1), (1, -1)].map(Coord2D::from) {
+            for line in Self::lines(grid, dir, wrap) {
+                let scan_len = if wrap {
+                    line.len() + maxlen.saturating_sub(1)
+                } else {
+                    line.len()
+                };
+                let mut node = 0;
+                for end in 0..scan_len {
+                    node = self.step(node, grid.get_c(line[end % line.len()]));
+                    for (idx, reversed) in &self.nodes[node].out {
+                        let len = self.words[*idx].len();
+                        let begin = end + 1 - len;
+                        if begin >= line.len() {
+                            continue;
+                        }
+                        hits.push(if *reversed {
+                            Hit {
+                                word: self.words[*idx].iter().collect(),
+                                start: line[end % line.len()],
+                                dir: Coord2D::new(0, 0) - dir,
+                            }
+                        } else {
+                            Hit {
+                                word: self.words[*idx].iter().collect(),
+                                start: line[begin],
+                                dir,
+                            }
+                        });
+                    }
+                }
+            }
+        }
+        hits
+    }
 }
 
 fn part1(input: &[String]) -> usize {
     let grid = mkgrid(input);
-    grid.x_bounds_orig()
-        .cartesian_product(grid.y_bounds_orig())
-        .map(|(x, y)| num_words_at(x, y, &grid))
-        .sum()
+    WordSearch::new(&["XMAS"]).find_all(&grid, false).len()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct StencilMatch {
+    origin: Coord2D,
+    variant: usize,
+}
+
+struct Stencil {
+    variants: Vec<Vec<(i64, i64, char)>>,
 }
 
-fn x_mas_at(x: i64, y: i64, grid: &Grid<char>) -> bool {
-    let center = Coord2D::new(x, y);
-    if grid.get_c(center) != 'A' {
-        return false;
+impl Stencil {
+    fn new(template: &str) -> Self {
+        let cells: Vec<(i64, i64, char)> = template
+            .split(['/', '\n'])
+            .map(|row| row.trim())
+            .filter(|row| !row.is_empty())
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.chars()
+                    .enumerate()
+                    .filter(|(_, c)| *c != '.')
+                    .map(move |(x, c)| (x as i64, y as i64, c))
+            })
+            .collect();
+        let mut variants: Vec<Vec<(i64, i64, char)>> = Vec::new();
+        for reflect in [false, true] {
+            for rot in 0..4 {
+                let mut v: Vec<(i64, i64, char)> = cells
+                    .iter()
+                    .map(|&(x, y, c)| {
+                        let (mut x, mut y) = if reflect { (-x, y) } else { (x, y) };
+                        for _ in 0..rot {
+                            (x, y) = (-y, x);
+                        }
+                        (x, y, c)
+                    })
+                    .collect();
+                let min_x = v.iter().map(|c| c.0).min().unwrap_or(0);
+                let min_y = v.iter().map(|c| c.1).min().unwrap_or(0);
+                v.iter_mut().for_each(|c| {
+                    c.0 -= min_x;
+                    c.1 -= min_y;
+                });
+                v.sort();
+                if !variants.contains(&v) {
+                    variants.push(v);
+                }
+            }
+        }
+        Self { variants }
+    }
+
+    fn matches_at(&self, variant: usize, origin: Coord2D, grid: &Grid<char>) -> bool {
+        self.variants[variant]
+            .iter()
+            .all(|&(x, y, c)| grid.get_c(origin + Coord2D::new(x, y)) == c)
+    }
+
+    fn cells(&self, m: &StencilMatch) -> Vec<Coord2D> {
+        self.variants[m.variant]
+            .iter()
+            .map(|&(x, y, _)| m.origin + Coord2D::new(x, y))
+            .collect()
+    }
+
+    fn find(&self, grid: &Grid<char>) -> Vec<StencilMatch> {
+        grid.y_bounds_orig()
+            .cartesian_product(grid.x_bounds_orig())
+            .flat_map(|(y, x)| {
+                (0..self.variants.len())
+                    .filter(move |v| self.matches_at(*v, Coord2D::new(x, y), grid))
+                    .map(move |variant| StencilMatch {
+                        origin: Coord2D::new(x, y),
+                        variant,
+                    })
+            })
+            .collect()
     }
-    let nw = center + Coord2D::new(1, 1);
-    let se = center + Coord2D::new(-1, -1);
-    let ne = center + Coord2D::new(1, -1);
-    let sw = center + Coord2D::new(-1, 1);
-    let nw = grid.get_c(nw);
-    let ne = grid.get_c(ne);
-    let sw = grid.get_c(sw);
-    let se = grid.get_c(se);
-    (nw == 'M' && se == 'S' || nw == 'S' && se == 'M')
-        && (ne == 'M' && sw == 'S' || ne == 'S' && sw == 'M')
 }
 
 fn part2(input: &[String]) -> usize {
     let grid = mkgrid(input);
-    grid.x_bounds_orig()
-        .cartesian_product(grid.y_bounds_orig())
-        .filter(|(x, y)| x_mas_at(*x, *y, &grid))
-        .count()
+    Stencil::new("M.S/.A./M.S").find(&grid).len()
+}
+
+fn highlight(grid: &Grid<char>, cells: &HashSet<Coord2D>) -> String {
+    grid.y_bounds_orig()
+        .map(|y| {
+            grid.x_bounds_orig()
+                .map(|x| {
+                    if cells.contains(&Coord2D::new(x, y)) {
+                        grid.get(x, y)
+                    } else {
+                        '.'
+                    }
+                })
+                .chain(['\n'])
+                .collect::<String>()
+        })
+        .collect()
+}
+
+fn render_part1(input: &[String]) -> (String, Vec<(&'static str, usize)>) {
+    let grid = mkgrid(input);
+    let hits = WordSearch::new(&["XMAS"]).find_all(&grid, false);
+    let cells: HashSet<Coord2D> = hits.iter().flat_map(|h| h.cells()).collect();
+    let counts = hits.iter().counts_by(|h| h.dir_name());
+    let by_dir = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"]
+        .into_iter()
+        .map(|d| (d, counts.get(d).copied().unwrap_or(0)))
+        .collect();
+    (highlight(&grid, &cells), by_dir)
+}
+
+fn render_part2(input: &[String]) -> String {
+    let grid = mkgrid(input);
+    let stencil = Stencil::new("M.S/.A./M.S");
+    let cells: HashSet<Coord2D> = stencil
+        .find(&grid)
+        .iter()
+        .flat_map(|m| stencil.cells(m))
+        .collect();
+    highlight(&grid, &cells)
 }
 
 fn main() {
@@ -68,6 +318,15 @@ fn main() {
     let part2 = part2(&input);
     let duration = start.elapsed();
     println!("Part 2: {part2} ({duration:?})");
+    if std::env::var_os("RENDER").is_some() {
+        let (grid, by_dir) = render_part1(&input);
+        print!("{grid}");
+        println!(
+            "{}",
+            by_dir.iter().map(|(d, n)| format!("{d}: {n}")).join(", ")
+        );
+        print!("{}", render_part2(&input));
+    }
 }
 
 #[cfg(test)]
@@ -75,10 +334,7 @@ mod tests {
     use super::*;
     use ya_advent_lib::read::test_input;
 
-    #[test]
-    fn day04_test() {
-        let input: Vec<String> = test_input(
-            "MMMSXXMASM
+    const EXAMPLE: &str = "MMMSXXMASM
 MSAMXMSMSA
 AMXSXMAAMM
 MSAMASMSMX
@@ -88,9 +344,90 @@ SMSMSASXSS
 SAXAMASAAA
 MAMMMXMMMM
 MXMXAXMASX
-",
-        );
+";
+
+    #[test]
+    fn day04_test() {
+        let input: Vec<String> = test_input(EXAMPLE);
         assert_eq!(part1(&input), 18);
         assert_eq!(part2(&input), 9);
     }
+
+    #[test]
+    fn day04_wordsearch_test() {
+        let input: Vec<String> = test_input(EXAMPLE);
+        let grid = mkgrid(&input);
+        let hits = WordSearch::new(&["XMAS", "SAMX", "MAS"]).find_all(&grid, false);
+        assert_eq!(hits.iter().filter(|h| h.word == "XMAS").count(), 18);
+        assert_eq!(hits.iter().filter(|h| h.word == "SAMX").count(), 18);
+
+        let grid = mkgrid(&test_input("MASX\n....\n....\n....\n"));
+        assert!(WordSearch::new(&["XMAS"]).find_all(&grid, false).is_empty());
+        assert_eq!(
+            WordSearch::new(&["XMAS"]).find_all(&grid, true),
+            vec![Hit {
+                word: "XMAS".into(),
+                start: Coord2D::new(3, 0),
+                dir: Coord2D::new(1, 0),
+            }]
+        );
+    }
+
+    #[test]
+    fn day04_stencil_test() {
+        assert_eq!(Stencil::new("M.S/.A./M.S").variants.len(), 4);
+        assert_eq!(Stencil::new("XMAS").variants.len(), 4);
+        assert_eq!(Stencil::new("XM\n.A").variants.len(), 8);
+        let grid = mkgrid(&test_input(".X.\nAM.\n..."));
+        let found = Stencil::new("XM\n.A").find(&grid);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].origin, Coord2D::new(0, 0));
+    }
+
+    #[test]
+    fn day04_render_test() {
+        let input: Vec<String> = test_input(EXAMPLE);
+        let (rendered, by_dir) = render_part1(&input);
+        assert_eq!(
+            rendered,
+            "....XXMAS.
+.SAMXMS...
+...S..A...
+..A.A.MS.X
+XMASAMX.MM
+X.....XA.A
+S.S.S.S.SS
+.A.A.A.A.A
+..M.M.M.MM
+.X.X.XMASX
+"
+        );
+        assert_eq!(
+            by_dir,
+            vec![
+                ("N", 2),
+                ("NE", 4),
+                ("E", 3),
+                ("SE", 1),
+                ("S", 1),
+                ("SW", 1),
+                ("W", 2),
+                ("NW", 4)
+            ]
+        );
+        assert_eq!(
+            render_part2(&input),
+            ".M.S......
+..A..MSMS.
+.M.S.MAA..
+..A.ASMSM.
+.M.S.M....
+..........
+S.S.S.S.S.
+.A.A.A.A..
+M.M.M.M.M.
+..........
+"
+        );
+    }
 }
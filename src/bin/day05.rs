@@ -1,10 +1,13 @@
-use std::collections::HashSet;
+use itertools::Itertools;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt;
 use std::str::FromStr;
 use std::time::Instant;
 use std::vec::Vec;
-use topological_sort::TopologicalSort;
 use ya_advent_lib::read::read_sectioned_input;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 struct OrderRule {
     earlier: usize,
     later: usize,
@@ -31,46 +34,322 @@ impl FromStr for PageList {
     }
 }
 
+#[derive(Debug, PartialEq)]
+struct Violation {
+    rule: OrderRule,
+    earlier_pos: usize,
+    later_pos: usize,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "rule {}|{} violated: {} at position {} comes after {} at position {}",
+            self.rule.earlier,
+            self.rule.later,
+            self.rule.earlier,
+            self.earlier_pos,
+            self.rule.later,
+            self.later_pos,
+        )
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Move {
+    page: usize,
+    from: usize,
+    to: usize,
+}
+
+#[derive(Debug, PartialEq)]
+struct Cycle(Vec<usize>);
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ordering rules form a cycle: ")?;
+        for n in &self.0 {
+            write!(f, "{n} -> ")?;
+        }
+        write!(f, "{}", self.0[0])
+    }
+}
+
+struct RuleIndex {
+    size: usize,
+    bits: Vec<u64>,
+}
+
+impl RuleIndex {
+    fn new(rules: &HashSet<(usize, usize)>) -> Self {
+        let size = rules.iter().map(|(a, b)| a.max(b) + 1).max().unwrap_or(0);
+        let mut bits = vec![0u64; (size * size).div_ceil(64)];
+        for (a, b) in rules {
+            let i = a * size + b;
+            bits[i / 64] |= 1 << (i % 64);
+        }
+        Self { size, bits }
+    }
+
+    fn before(&self, a: usize, b: usize) -> bool {
+        if a >= self.size || b >= self.size {
+            return false;
+        }
+        let i = a * self.size + b;
+        self.bits[i / 64] & (1 << (i % 64)) != 0
+    }
+}
+
 struct Rules {
     rules: HashSet<(usize, usize)>,
+    index: RuleIndex,
 }
 
 impl Rules {
     fn from_orders(orders: &[OrderRule]) -> Self {
         let rules: HashSet<(usize, usize)> = orders.iter().map(|o| (o.earlier, o.later)).collect();
-        Self { rules }
+        let index = RuleIndex::new(&rules);
+        Self { rules, index }
     }
 
-    fn sort_nodes(&self, nodes: &[usize]) -> Vec<usize> {
-        let nodeset: HashSet<usize> = HashSet::from_iter(nodes.iter().copied());
-        let mut topo: TopologicalSort<usize> = TopologicalSort::new();
-        self.rules
+    #[allow(dead_code)]
+    fn to_dot(&self) -> String {
+        let mut out = String::from("digraph rules {\n");
+        for (a, b) in self.rules.iter().sorted() {
+            out.push_str(&format!("    {a} -> {b};\n"));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    #[allow(dead_code)]
+    fn update_to_dot(&self, pages: &PageList) -> String {
+        let mut out = String::from("digraph update {\n");
+        for n in &pages.0 {
+            out.push_str(&format!("    {n};\n"));
+        }
+        let pos: HashMap<usize, usize> = pages.0.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+        for (a, b) in self
+            .rules
             .iter()
-            .filter(|o| nodeset.contains(&o.0) && nodeset.contains(&o.1))
-            .for_each(|o| topo.add_dependency(o.0, o.1));
-        topo.collect()
+            .filter(|(a, b)| pos.contains_key(a) && pos.contains_key(b))
+            .sorted()
+        {
+            if pos[a] > pos[b] {
+                out.push_str(&format!("    {a} -> {b} [color=red];\n"));
+            } else {
+                out.push_str(&format!("    {a} -> {b};\n"));
+            }
+        }
+        for (a, b) in pages.0.iter().tuple_windows() {
+            out.push_str(&format!(
+                "    {a} -> {b} [style=dashed, color=blue, constraint=false];\n"
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn violations(&self, pages: &PageList) -> Vec<Violation> {
+        let mut out = Vec::new();
+        for (later_pos, later) in pages.0.iter().enumerate() {
+            for (earlier_pos, earlier) in pages.0.iter().enumerate().skip(later_pos + 1) {
+                if self.index.before(*earlier, *later) {
+                    out.push(Violation {
+                        rule: OrderRule {
+                            earlier: *earlier,
+                            later: *later,
+                        },
+                        earlier_pos,
+                        later_pos,
+                    });
+                }
+            }
+        }
+        out
+    }
+
+    fn sort_nodes(&self, nodes: &[usize]) -> Result<Vec<usize>, Cycle> {
+        let n = nodes.len();
+        let mut indegree: Vec<usize> = (0..n)
+            .map(|j| {
+                (0..n)
+                    .filter(|i| self.index.before(nodes[*i], nodes[j]))
+                    .count()
+            })
+            .collect();
+        let mut placed = vec![false; n];
+        let mut sorted = Vec::with_capacity(n);
+        while sorted.len() < n {
+            let Some(i) = (0..n).find(|i| !placed[*i] && indegree[*i] == 0) else {
+                return Err(self.find_cycle(nodes).unwrap());
+            };
+            placed[i] = true;
+            sorted.push(nodes[i]);
+            (0..n)
+                .filter(|j| self.index.before(nodes[i], nodes[*j]))
+                .for_each(|j| indegree[j] -= 1);
+        }
+        Ok(sorted)
+    }
+
+    #[allow(dead_code)]
+    fn fix_min_moves(&self, pages: &PageList) -> Result<(Vec<usize>, Vec<Move>), Cycle> {
+        let nodes = &pages.0;
+        let n = nodes.len();
+        let mut reach: Vec<Vec<bool>> = (0..n)
+            .map(|i| {
+                (0..n)
+                    .map(|j| self.index.before(nodes[i], nodes[j]))
+                    .collect()
+            })
+            .collect();
+        for k in 0..n {
+            let via = reach[k].clone();
+            reach
+                .iter_mut()
+                .filter(|row| row[k])
+                .for_each(|row| row.iter_mut().zip(&via).for_each(|(r, v)| *r |= v));
+        }
+        if (0..n).any(|i| reach[i][i]) {
+            return Err(self.find_cycle(nodes).unwrap());
+        }
+
+        // Positions i < j are inverted when page j must precede page i. Inversions
+        // form a partial order on positions, so the largest set of pages that can
+        // stay put is a maximum antichain, found via matching (Dilworth/König).
+        let inverted: Vec<Vec<usize>> = (0..n)
+            .map(|i| (i + 1..n).filter(|j| reach[*j][i]).collect())
+            .collect();
+        let mut match_r: Vec<Option<usize>> = vec![None; n];
+        for u in 0..n {
+            augment(u, &inverted, &mut vec![false; n], &mut match_r);
+        }
+        let mut matched_l = vec![false; n];
+        match_r.iter().flatten().for_each(|u| matched_l[*u] = true);
+        let mut z_left = vec![false; n];
+        let mut z_right = vec![false; n];
+        let mut queue: Vec<usize> = (0..n).filter(|u| !matched_l[*u]).collect();
+        queue.iter().for_each(|u| z_left[*u] = true);
+        while let Some(u) = queue.pop() {
+            for v in &inverted[u] {
+                if !z_right[*v] {
+                    z_right[*v] = true;
+                    if let Some(w) = match_r[*v] {
+                        if !z_left[w] {
+                            z_left[w] = true;
+                            queue.push(w);
+                        }
+                    }
+                }
+            }
+        }
+        let kept: Vec<usize> = (0..n).filter(|x| z_left[*x] && !z_right[*x]).collect();
+
+        let mut succ: Vec<Vec<usize>> = (0..n)
+            .map(|i| (0..n).filter(|j| reach[i][*j]).collect())
+            .collect();
+        kept.windows(2).for_each(|w| succ[w[0]].push(w[1]));
+        let mut indegree = vec![0; n];
+        succ.iter().flatten().for_each(|j| indegree[*j] += 1);
+        let mut heap: BinaryHeap<Reverse<usize>> =
+            (0..n).filter(|i| indegree[*i] == 0).map(Reverse).collect();
+        let mut order: Vec<usize> = Vec::with_capacity(n);
+        while let Some(Reverse(i)) = heap.pop() {
+            order.push(i);
+            for j in &succ[i] {
+                indegree[*j] -= 1;
+                if indegree[*j] == 0 {
+                    heap.push(Reverse(*j));
+                }
+            }
+        }
+        let moves = order
+            .iter()
+            .enumerate()
+            .filter(|(_, from)| !kept.contains(from))
+            .map(|(to, from)| Move {
+                page: nodes[*from],
+                from: *from,
+                to,
+            })
+            .collect();
+        Ok((order.iter().map(|i| nodes[*i]).collect(), moves))
+    }
+
+    fn find_cycle(&self, nodes: &[usize]) -> Option<Cycle> {
+        let mut succ: HashMap<usize, Vec<usize>> = HashMap::new();
+        for n in nodes {
+            for m in nodes {
+                if self.index.before(*n, *m) {
+                    succ.entry(*n).or_default().push(*m);
+                }
+            }
+        }
+        let mut done: HashSet<usize> = HashSet::new();
+        for start in nodes {
+            if done.contains(start) {
+                continue;
+            }
+            let mut path: Vec<usize> = vec![*start];
+            let mut stack: Vec<std::slice::Iter<usize>> =
+                vec![succ.get(start).map(|v| v.iter()).unwrap_or_default()];
+            while let Some(iter) = stack.last_mut() {
+                match iter.next() {
+                    Some(next) if done.contains(next) => {}
+                    Some(next) => {
+                        if let Some(pos) = path.iter().position(|n| n == next) {
+                            return Some(Cycle(path[pos..].to_vec()));
+                        }
+                        path.push(*next);
+                        stack.push(succ.get(next).map(|v| v.iter()).unwrap_or_default());
+                    }
+                    None => {
+                        done.insert(path.pop().unwrap());
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        None
     }
 }
 
-fn bothparts(orders: &[OrderRule], pages: &[PageList]) -> (usize, usize) {
-    let rules = Rules::from_orders(orders);
-    pages
-        .iter()
-        .map(|lst| {
-            let sorted = rules.sort_nodes(&lst.0);
-            if sorted == lst.0 {
-                (lst.0[lst.0.len() / 2], 0)
-            } else {
-                (0, sorted[sorted.len() / 2])
+fn augment(u: usize, adj: &[Vec<usize>], seen: &mut [bool], match_r: &mut [Option<usize>]) -> bool {
+    for v in &adj[u] {
+        if !seen[*v] {
+            seen[*v] = true;
+            if match_r[*v].is_none_or(|w| augment(w, adj, seen, match_r)) {
+                match_r[*v] = Some(u);
+                return true;
             }
-        })
-        .fold((0, 0), |acc, x| (acc.0 + x.0, acc.1 + x.1))
+        }
+    }
+    false
+}
+
+fn bothparts(orders: &[OrderRule], pages: &[PageList]) -> Result<(usize, usize), Cycle> {
+    let rules = Rules::from_orders(orders);
+    pages.iter().try_fold((0, 0), |acc, lst| {
+        if rules.violations(lst).is_empty() {
+            return Ok((acc.0 + lst.0[lst.0.len() / 2], acc.1));
+        }
+        let sorted = rules.sort_nodes(&lst.0)?;
+        Ok((acc.0, acc.1 + sorted[sorted.len() / 2]))
+    })
 }
 
 fn main() {
     let input: (Vec<OrderRule>, Vec<PageList>) = read_sectioned_input();
     let start = Instant::now();
-    let (part1, part2) = bothparts(&input.0, &input.1);
+    let (part1, part2) = match bothparts(&input.0, &input.1) {
+        Ok(parts) => parts,
+        Err(cycle) => {
+            eprintln!("{cycle}");
+            std::process::exit(1);
+        }
+    };
     let duration = start.elapsed();
     println!("Part 1: {part1}");
     println!("Part 2: {part2}");
@@ -80,14 +359,215 @@ fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use topological_sort::TopologicalSort;
     use ya_advent_lib::read::sectioned_test_input;
 
+    impl Rules {
+        fn sort_nodes_topo(&self, nodes: &[usize]) -> Result<Vec<usize>, Cycle> {
+            let nodeset: HashSet<usize> = HashSet::from_iter(nodes.iter().copied());
+            let mut topo: TopologicalSort<usize> = TopologicalSort::new();
+            nodes.iter().for_each(|n| {
+                topo.insert(*n);
+            });
+            self.rules
+                .iter()
+                .filter(|o| nodeset.contains(&o.0) && nodeset.contains(&o.1))
+                .for_each(|o| topo.add_dependency(o.0, o.1));
+            let sorted: Vec<usize> = topo.by_ref().collect();
+            if topo.is_empty() {
+                Ok(sorted)
+            } else {
+                Err(self.find_cycle(nodes).unwrap())
+            }
+        }
+    }
+
+    fn example() -> (Vec<OrderRule>, Vec<PageList>) {
+        sectioned_test_input(include_str!("day05.testinput"))
+    }
+
+    fn cyclic_orders() -> Vec<OrderRule> {
+        ["1|2", "2|3", "3|1", "3|4", "5|1"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect()
+    }
+
+    fn chain_orders() -> Vec<OrderRule> {
+        ["3|2", "2|1"].iter().map(|s| s.parse().unwrap()).collect()
+    }
+
     #[test]
     fn day05_test() {
         let input: (Vec<OrderRule>, Vec<PageList>) =
             sectioned_test_input(include_str!("day05.testinput"));
-        let (part1, part2) = bothparts(&input.0, &input.1);
+        let (part1, part2) = bothparts(&input.0, &input.1).unwrap();
         assert_eq!(part1, 143);
         assert_eq!(part2, 123);
     }
+
+    #[test]
+    fn day05_cycle_test() {
+        let orders = cyclic_orders();
+        let rules = Rules::from_orders(&orders);
+        let cycle = rules.sort_nodes(&[4, 1, 2, 3, 5]).unwrap_err();
+        assert_eq!(cycle, Cycle(vec![1, 2, 3]));
+        assert_eq!(
+            cycle.to_string(),
+            "ordering rules form a cycle: 1 -> 2 -> 3 -> 1"
+        );
+        let pages = vec![PageList(vec![4, 1, 2, 3, 5]), PageList(vec![2, 1, 5])];
+        assert_eq!(bothparts(&orders, &pages), Err(Cycle(vec![1, 2, 3])));
+        assert_eq!(bothparts(&orders, &pages[1..]), Ok((0, 1)));
+        assert_eq!(
+            rules.fix_min_moves(&PageList(vec![1, 2, 3])).unwrap_err(),
+            Cycle(vec![1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn day05_violations_test() {
+        let input = example();
+        let rules = Rules::from_orders(&input.0);
+        assert!(rules.violations(&input.1[0]).is_empty());
+        let v = rules.violations(&input.1[3]);
+        assert_eq!(
+            v,
+            vec![Violation {
+                rule: OrderRule {
+                    earlier: 97,
+                    later: 75
+                },
+                earlier_pos: 1,
+                later_pos: 0,
+            }]
+        );
+        assert_eq!(
+            v[0].to_string(),
+            "rule 97|75 violated: 97 at position 1 comes after 75 at position 0"
+        );
+        assert_eq!(rules.violations(&input.1[4]).len(), 1);
+        assert_eq!(rules.violations(&input.1[5]).len(), 4);
+    }
+
+    #[test]
+    fn day05_min_moves_test() {
+        let input = example();
+        let rules = Rules::from_orders(&input.0);
+        let (fixed, moves) = rules.fix_min_moves(&input.1[0]).unwrap();
+        assert_eq!(fixed, input.1[0].0);
+        assert!(moves.is_empty());
+        let (fixed, moves) = rules.fix_min_moves(&input.1[3]).unwrap();
+        assert_eq!(fixed, vec![97, 75, 47, 61, 53]);
+        assert_eq!(moves.len(), 1);
+        let (fixed, moves) = rules.fix_min_moves(&input.1[4]).unwrap();
+        assert_eq!(fixed, vec![61, 29, 13]);
+        assert_eq!(moves.len(), 1);
+        let (fixed, moves) = rules.fix_min_moves(&input.1[5]).unwrap();
+        assert_eq!(fixed, vec![97, 75, 47, 29, 13]);
+        assert_eq!(moves.len(), 2);
+
+        let rules = Rules::from_orders(&chain_orders());
+        let (fixed, moves) = rules.fix_min_moves(&PageList(vec![1, 4, 3])).unwrap();
+        assert_eq!(fixed, vec![1, 4, 3]);
+        assert!(moves.is_empty());
+        let (fixed, moves) = rules.fix_min_moves(&PageList(vec![1, 2, 3, 4])).unwrap();
+        assert_eq!(fixed, vec![3, 2, 1, 4]);
+        assert_eq!(moves.len(), 2);
+        let (fixed, moves) = rules.fix_min_moves(&PageList(vec![4, 1, 3])).unwrap();
+        assert_eq!(fixed, vec![4, 1, 3]);
+        assert_eq!(moves, vec![]);
+        let (fixed, moves) = rules.fix_min_moves(&PageList(vec![2, 1, 3])).unwrap();
+        assert_eq!(fixed, vec![3, 2, 1]);
+        assert_eq!(
+            moves,
+            vec![Move {
+                page: 3,
+                from: 2,
+                to: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn day05_dot_test() {
+        let input = example();
+        let rules = Rules::from_orders(&input.0);
+        assert_eq!(
+            rules.update_to_dot(&input.1[4]),
+            "digraph update {
+    61;
+    13;
+    29;
+    29 -> 13 [color=red];
+    61 -> 13;
+    61 -> 29;
+    61 -> 13 [style=dashed, color=blue, constraint=false];
+    13 -> 29 [style=dashed, color=blue, constraint=false];
+}
+"
+        );
+        let rules = Rules::from_orders(&chain_orders());
+        assert_eq!(
+            rules.to_dot(),
+            "digraph rules {\n    2 -> 1;\n    3 -> 2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn day05_index_test() {
+        let rules = Rules::from_orders(&cyclic_orders());
+        assert_eq!(rules.sort_nodes(&[5, 2, 1]), Ok(vec![5, 1, 2]));
+        assert_eq!(rules.sort_nodes(&[4, 9, 3]), Ok(vec![9, 3, 4]));
+        let sorted = rules.sort_nodes_topo(&[4, 9, 3]).unwrap();
+        assert_eq!(sorted.len(), 3);
+        assert!(sorted.iter().position(|n| *n == 3) < sorted.iter().position(|n| *n == 4));
+        assert_eq!(
+            rules.sort_nodes_topo(&[4, 1, 2, 3, 5]).unwrap_err(),
+            Cycle(vec![1, 2, 3])
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn day05_bench() {
+        use rand::rngs::StdRng;
+        use rand::seq::SliceRandom;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut pages: Vec<usize> = (10..100).collect();
+        pages.shuffle(&mut rng);
+        pages.truncate(49);
+        let orders: Vec<OrderRule> = pages
+            .iter()
+            .tuple_combinations()
+            .map(|(a, b)| OrderRule {
+                earlier: *a,
+                later: *b,
+            })
+            .collect();
+        let updates: Vec<Vec<usize>> = (0..200)
+            .map(|_| {
+                let len = rng.gen_range(2..12) * 2 + 1;
+                pages.choose_multiple(&mut rng, len).copied().collect()
+            })
+            .collect();
+        let rules = Rules::from_orders(&orders);
+
+        let start = Instant::now();
+        let by_topo: Vec<Vec<usize>> = updates
+            .iter()
+            .map(|u| rules.sort_nodes_topo(u).unwrap())
+            .collect();
+        let topo_duration = start.elapsed();
+        let start = Instant::now();
+        let by_index: Vec<Vec<usize>> = updates
+            .iter()
+            .map(|u| rules.sort_nodes(u).unwrap())
+            .collect();
+        let index_duration = start.elapsed();
+        println!("TopologicalSort: {topo_duration:?}, rule index: {index_duration:?}");
+        assert_eq!(by_topo, by_index);
+    }
 }
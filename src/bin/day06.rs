@@ -1,5 +1,5 @@
 use itertools::Itertools;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::time::Instant;
 use std::vec::Vec;
 use ya_advent_lib::coords::{CDir, Coord2D};
@@ -10,87 +10,324 @@ use ya_advent_lib::read::read_input;
 enum Cell {
     Empty,
     Wall,
-    Start,
+    Guard(CDir),
 }
 
 impl From<char> for Cell {
     fn from(c: char) -> Self {
         match c {
             '#' => Cell::Wall,
-            '^' => Cell::Start,
+            '^' => Cell::Guard(CDir::N),
+            '>' => Cell::Guard(CDir::E),
+            'v' => Cell::Guard(CDir::S),
+            '<' => Cell::Guard(CDir::W),
             _ => Cell::Empty,
         }
     }
 }
 
-fn part1(input: &[String]) -> usize {
-    let grid = Grid::from_input(input, Cell::Empty, 0);
-    let mut pos: Coord2D = grid.find(|c, _, _| c == Cell::Start).unwrap().into();
-    let mut dir = CDir::N;
-    let mut stepped: HashSet<Coord2D> = HashSet::new();
-    loop {
-        stepped.insert(pos);
-        let next = pos + dir;
+fn guard_glyph(dir: CDir) -> char {
+    match dir {
+        CDir::N => '^',
+        CDir::E => '>',
+        CDir::S => 'v',
+        CDir::W => '<',
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+enum Turn {
+    Right,
+    Left,
+    Reverse,
+}
+
+impl Turn {
+    fn apply(&self, dir: CDir) -> CDir {
+        match self {
+            Turn::Right => dir.right(),
+            Turn::Left => dir.left(),
+            Turn::Reverse => dir.reverse(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Exits,
+    Loops,
+}
+
+#[allow(dead_code)]
+struct GuardReport {
+    start: Coord2D,
+    dir: CDir,
+    visited: HashSet<Coord2D>,
+    outcome: Outcome,
+}
+
+fn find_guards(grid: &Grid<Cell>) -> Vec<(Coord2D, CDir)> {
+    grid.iter_with_coord()
+        .filter_map(|(c, x, y)| match c {
+            Cell::Guard(dir) => Some((Coord2D::new(x, y), dir)),
+            _ => None,
+        })
+        .sorted_by_key(|(c, _)| (c.y, c.x))
+        .collect()
+}
+
+fn patrol(grid: &Grid<Cell>, start: Coord2D, dir: CDir, turn: Turn) -> GuardReport {
+    let mut pos = start;
+    let mut cur_dir = dir;
+    let mut visited: HashSet<Coord2D> = HashSet::new();
+    let mut turns: HashSet<(Coord2D, CDir)> = HashSet::new();
+    let outcome = loop {
+        visited.insert(pos);
+        let next = pos + cur_dir;
         if !grid.contains_coord(next) {
-            break;
+            break Outcome::Exits;
         }
         match grid.get_c(next) {
             Cell::Wall => {
-                dir = dir.right();
+                if !turns.insert((pos, cur_dir)) {
+                    break Outcome::Loops;
+                }
+                cur_dir = turn.apply(cur_dir);
             }
             _ => {
                 pos = next;
             }
         }
+    };
+    GuardReport {
+        start,
+        dir,
+        visited,
+        outcome,
+    }
+}
+
+fn patrol_all(input: &[String], turn: Turn) -> Vec<GuardReport> {
+    let grid = Grid::from_input(input, Cell::Empty, 0);
+    find_guards(&grid)
+        .into_iter()
+        .map(|(start, dir)| patrol(&grid, start, dir, turn))
+        .collect()
+}
+
+fn part1(input: &[String]) -> usize {
+    patrol_all(input, Turn::Right)[0].visited.len()
+}
+
+fn dir_idx(dir: CDir) -> usize {
+    match dir {
+        CDir::N => 0,
+        CDir::E => 1,
+        CDir::S => 2,
+        CDir::W => 3,
     }
-    stepped.len()
 }
 
-trait GuardExits {
-    fn guard_exits(&self, start: Coord2D, obstacle: Coord2D) -> bool;
+#[derive(Debug, PartialEq)]
+struct GuardLoop {
+    guard: Coord2D,
+    obstacle: Coord2D,
+    turns: Vec<Coord2D>,
+    length: i64,
+}
+
+struct JumpTable {
+    x0: i64,
+    y0: i64,
+    width: i64,
+    stops: Vec<Option<Coord2D>>,
 }
 
-impl GuardExits for Grid<Cell> {
-    fn guard_exits(&self, start: Coord2D, obstacle: Coord2D) -> bool {
-        let mut dir = CDir::N;
+impl JumpTable {
+    fn new(grid: &Grid<Cell>) -> Self {
+        let x0 = grid.x_bounds().start;
+        let y0 = grid.y_bounds().start;
+        let width = grid.x_bounds().end - x0;
+        let height = grid.y_bounds().end - y0;
+        let mut table = Self {
+            x0,
+            y0,
+            width,
+            stops: vec![None; (width * height * 4) as usize],
+        };
+        let cells: Vec<Coord2D> = grid
+            .y_bounds()
+            .cartesian_product(grid.x_bounds())
+            .map(|(y, x)| Coord2D::new(x, y))
+            .collect();
+        for dir in [CDir::N, CDir::E, CDir::S, CDir::W] {
+            let delta = Coord2D::new(0, 0) + dir;
+            let mut ordered = cells.clone();
+            ordered.sort_by_key(|c| -(c.x * delta.x + c.y * delta.y));
+            for pos in ordered {
+                let next = pos + dir;
+                let stop = if !grid.contains_coord(next) {
+                    None
+                } else if grid.get_c(next) == Cell::Wall {
+                    Some(pos)
+                } else {
+                    table.stops[table.idx(next, dir)]
+                };
+                let i = table.idx(pos, dir);
+                table.stops[i] = stop;
+            }
+        }
+        table
+    }
+
+    fn idx(&self, pos: Coord2D, dir: CDir) -> usize {
+        (((pos.y - self.y0) * self.width + (pos.x - self.x0)) * 4) as usize + dir_idx(dir)
+    }
+
+    fn jump(&self, pos: Coord2D, dir: CDir, obstacle: Coord2D) -> Option<Coord2D> {
+        let stop = self.stops[self.idx(pos, dir)];
+        let delta = Coord2D::new(0, 0) + dir;
+        let to_obstacle = obstacle - pos;
+        let dist = to_obstacle.x * delta.x + to_obstacle.y * delta.y;
+        if dist < 1 || delta * dist != to_obstacle {
+            return stop;
+        }
+        match stop {
+            Some(s) if (s - pos).x.abs() + (s - pos).y.abs() < dist => stop,
+            _ => Some(obstacle - delta),
+        }
+    }
+
+    fn guard_cycle(
+        &self,
+        start: Coord2D,
+        dir: CDir,
+        obstacle: Coord2D,
+        turn: Turn,
+    ) -> Option<Vec<Coord2D>> {
+        let mut dir = dir;
         let mut pos = start;
+        let mut turns: HashMap<(Coord2D, CDir), usize> = HashMap::new();
+        let mut path: Vec<Coord2D> = Vec::new();
+        while let Some(stop) = self.jump(pos, dir, obstacle) {
+            if let Some(idx) = turns.get(&(stop, dir)) {
+                let mut cycle = path.split_off(*idx);
+                cycle.dedup();
+                if cycle.len() > 1 && cycle[0] == cycle[cycle.len() - 1] {
+                    cycle.pop();
+                }
+                return Some(cycle);
+            }
+            turns.insert((stop, dir), path.len());
+            path.push(stop);
+            pos = stop;
+            dir = turn.apply(dir);
+        }
+        None
+    }
+}
+
+fn find_loops(input: &[String], turn: Turn) -> Vec<GuardLoop> {
+    let grid = Grid::from_input(input, Cell::Empty, 0);
+    let table = JumpTable::new(&grid);
+    let mut loops = Vec::new();
+    for (guard, start_dir) in find_guards(&grid) {
+        let (mut pos, mut dir) = (guard, start_dir);
+        let mut stepped: HashSet<Coord2D> = HashSet::from_iter([pos]);
         let mut turns: HashSet<(Coord2D, CDir)> = HashSet::new();
         loop {
             let next = pos + dir;
-            if !self.contains_coord(next) {
-                return true;
+            if !grid.contains_coord(next) {
+                break;
             }
-            let next_cell = if next == obstacle {
-                Cell::Wall
-            } else {
-                self.get_c(next)
-            };
-            match next_cell {
-                Cell::Wall => {
-                    if turns.contains(&(pos, dir)) {
-                        return false;
-                    }
-                    turns.insert((pos, dir));
-                    dir = dir.right();
+            if grid.get_c(next) == Cell::Wall {
+                if !turns.insert((pos, dir)) {
+                    break;
                 }
-                _ => {
-                    pos = next;
+                dir = turn.apply(dir);
+                continue;
+            }
+            if stepped.insert(next) {
+                if let Some(turns) = table.guard_cycle(pos, dir, next, turn) {
+                    let length = turns
+                        .iter()
+                        .circular_tuple_windows()
+                        .map(|(a, b)| (*b - *a).x.abs() + (*b - *a).y.abs())
+                        .sum();
+                    loops.push(GuardLoop {
+                        guard,
+                        obstacle: next,
+                        turns,
+                        length,
+                    });
                 }
             }
+            pos = next;
         }
     }
+    loops
 }
 
 fn part2(input: &[String]) -> usize {
-    let grid = Grid::from_input(input, Cell::Empty, 0);
-    let start: Coord2D = grid.find(|c, _, _| c == Cell::Start).unwrap().into();
-    grid.x_bounds()
-        .cartesian_product(grid.y_bounds())
-        .filter(|(x, y)| grid.get(*x, *y) == Cell::Empty)
-        .filter(|(x, y)| !grid.guard_exits(start, (*x, *y).into()))
+    find_loops(input, Turn::Right)
+        .iter()
+        .map(|l| l.obstacle)
+        .unique()
         .count()
 }
 
+#[allow(dead_code)]
+fn render_route(input: &[String], obstacle: Coord2D, turn: Turn) -> String {
+    const VERT: u8 = 1;
+    const HORIZ: u8 = 2;
+    const TURN: u8 = 4;
+    let grid = Grid::from_input(input, Cell::Empty, 0);
+    let guards = find_guards(&grid);
+    let mut marks: HashMap<Coord2D, u8> = HashMap::new();
+    for &(start, start_dir) in &guards {
+        let mut turns: HashSet<(Coord2D, CDir)> = HashSet::new();
+        let mut pos = start;
+        let mut dir = start_dir;
+        loop {
+            let next = pos + dir;
+            if !grid.contains_coord(next) {
+                break;
+            }
+            if next == obstacle || grid.get_c(next) == Cell::Wall {
+                *marks.entry(pos).or_default() |= TURN;
+                if !turns.insert((pos, dir)) {
+                    break;
+                }
+                dir = turn.apply(dir);
+                continue;
+            }
+            let axis = if matches!(dir, CDir::N | CDir::S) {
+                VERT
+            } else {
+                HORIZ
+            };
+            *marks.entry(pos).or_default() |= axis;
+            *marks.entry(next).or_default() |= axis;
+            pos = next;
+        }
+    }
+    let mut out: Grid<char> = Grid::from_input(input, '.', 0);
+    for (c, m) in marks {
+        let ch = match m {
+            VERT => '|',
+            HORIZ => '-',
+            _ => '+',
+        };
+        out.set_c(c, ch);
+    }
+    for (start, start_dir) in guards {
+        out.set_c(start, guard_glyph(start_dir));
+    }
+    out.set_c(obstacle, 'O');
+    out.format()
+}
+
 fn main() {
     let input: Vec<String> = read_input();
     let start = Instant::now();
@@ -108,10 +345,7 @@ mod tests {
     use super::*;
     use ya_advent_lib::read::test_input;
 
-    #[test]
-    fn day06_test() {
-        let input: Vec<String> = test_input(
-            "....#.....
+    const EXAMPLE: &str = "....#.....
 .........#
 ..........
 ..#.......
@@ -121,9 +355,137 @@ mod tests {
 ........#.
 #.........
 ......#...
-",
-        );
+";
+
+    const GUARDS: &str = ".#...
+.^..#
+>....
+#....
+...#.
+";
+
+    #[test]
+    fn day06_test() {
+        let input: Vec<String> = test_input(EXAMPLE);
         assert_eq!(part1(&input), 41);
         assert_eq!(part2(&input), 6);
     }
+
+    #[test]
+    fn day06_loops_test() {
+        let input: Vec<String> = test_input(EXAMPLE);
+        let loops = find_loops(&input, Turn::Right);
+        assert_eq!(
+            loops.iter().map(|l| l.obstacle).collect::<HashSet<_>>(),
+            HashSet::from_iter([(3, 6), (6, 7), (7, 7), (1, 8), (3, 8), (7, 9)].map(Coord2D::from))
+        );
+        assert_eq!(
+            loops.iter().find(|l| l.obstacle == Coord2D::new(3, 6)),
+            Some(&GuardLoop {
+                guard: Coord2D::new(4, 6),
+                obstacle: Coord2D::new(3, 6),
+                turns: [(4, 6), (4, 1), (8, 1), (8, 6)].map(Coord2D::from).to_vec(),
+                length: 18,
+            })
+        );
+    }
+
+    #[test]
+    fn day06_render_test() {
+        let input: Vec<String> = test_input(EXAMPLE);
+        assert_eq!(
+            render_route(&input, Coord2D::new(3, 6), Turn::Right),
+            "....#.....
+....+---+#
+....|...|.
+..#.|...|.
+....|..#|.
+....|...|.
+.#.O^---+.
+........#.
+#.........
+......#...
+"
+        );
+        assert_eq!(
+            render_route(&input, Coord2D::new(6, 7), Turn::Right),
+            "....#.....
+....+---+#
+....|...|.
+..#.|...|.
+..+-+-+#|.
+..|.|.|.|.
+.#+-^-+-+.
+......O.#.
+#.........
+......#...
+"
+        );
+    }
+
+    #[test]
+    fn day06_guards_test() {
+        let input: Vec<String> = test_input(GUARDS);
+        let reports = patrol_all(&input, Turn::Right);
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].start, Coord2D::new(1, 1));
+        assert_eq!(reports[0].dir, CDir::N);
+        assert_eq!(reports[0].visited.len(), 8);
+        assert_eq!(reports[0].outcome, Outcome::Loops);
+        assert_eq!(reports[1].start, Coord2D::new(0, 2));
+        assert_eq!(reports[1].dir, CDir::E);
+        assert_eq!(reports[1].visited.len(), 5);
+        assert_eq!(reports[1].outcome, Outcome::Exits);
+        let reports = patrol_all(&input, Turn::Left);
+        assert_eq!(reports[0].visited.len(), 2);
+        assert_eq!(reports[0].outcome, Outcome::Exits);
+        let reports = patrol_all(&input, Turn::Reverse);
+        assert_eq!(
+            reports[0].visited,
+            HashSet::from_iter([(1, 1), (1, 2), (1, 3), (1, 4)].map(Coord2D::from))
+        );
+        assert_eq!(reports[0].outcome, Outcome::Exits);
+    }
+
+    #[test]
+    fn day06_turns_test() {
+        let two_guards = EXAMPLE.replacen("..........\n.#..^", "....^.....\n.#..^", 1);
+        for input in [EXAMPLE, GUARDS, &two_guards] {
+            let input: Vec<String> = test_input(input);
+            let grid = Grid::from_input(&input, Cell::Empty, 0);
+            for turn in [Turn::Right, Turn::Left, Turn::Reverse] {
+                let mut expected = HashSet::new();
+                for (guard, dir) in find_guards(&grid) {
+                    for obstacle in patrol(&grid, guard, dir, turn).visited {
+                        if obstacle == guard {
+                            continue;
+                        }
+                        let mut blocked = grid.clone();
+                        blocked.set_c(obstacle, Cell::Wall);
+                        if patrol(&blocked, guard, dir, turn).outcome == Outcome::Loops {
+                            expected.insert((guard, obstacle));
+                        }
+                    }
+                }
+                let loops = find_loops(&input, turn);
+                assert_eq!(loops.len(), expected.len());
+                assert_eq!(
+                    loops
+                        .iter()
+                        .map(|l| (l.guard, l.obstacle))
+                        .collect::<HashSet<_>>(),
+                    expected
+                );
+            }
+        }
+        let input: Vec<String> = test_input(&two_guards);
+        assert_eq!(
+            find_loops(&input, Turn::Right)
+                .iter()
+                .map(|l| l.guard)
+                .unique()
+                .count(),
+            2
+        );
+    }
 }
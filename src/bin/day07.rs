@@ -1,63 +1,424 @@
-use itertools::{repeat_n, Itertools};
+use num::{BigInt, BigUint, Signed, ToPrimitive};
+use std::collections::HashSet;
+use std::fmt;
 use std::str::FromStr;
 use std::time::Instant;
 use std::vec::Vec;
 use ya_advent_lib::read::read_input;
 
-struct Input {
-    lhs: u64,
-    rhs: Vec<u64>,
+struct Input<V = u64> {
+    lhs: V,
+    rhs: Vec<V>,
 }
 
-impl FromStr for Input {
+impl<V: FromStr> FromStr for Input<V> {
     type Err = ();
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (lhs_s, rhs_s) = s.split_once(": ").unwrap();
-        let lhs = lhs_s.parse::<u64>().unwrap();
-        let rhs = rhs_s.split(' ').flat_map(|ss| ss.parse::<u64>()).collect();
+        let (lhs_s, rhs_s) = s.split_once(": ").ok_or(())?;
+        let lhs = lhs_s.parse::<V>().map_err(|_| ())?;
+        let rhs = rhs_s
+            .split(' ')
+            .map(|ss| ss.parse::<V>().map_err(|_| ()))
+            .collect::<Result<Vec<V>, _>>()?;
+        if rhs.is_empty() {
+            return Err(());
+        }
         Ok(Input { lhs, rhs })
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 enum Oper {
     Add,
+    Sub,
     Mul,
+    Div,
     Cat,
+    Pow,
+    Xor,
 }
 
-impl Input {
-    fn is_valid(&self, forpart2: bool) -> bool {
-        let ops = if forpart2 {
-            vec![Oper::Add, Oper::Mul, Oper::Cat]
-        } else {
-            vec![Oper::Add, Oper::Mul]
+impl FromStr for Oper {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "add" | "+" => Ok(Oper::Add),
+            "sub" | "-" => Ok(Oper::Sub),
+            "mul" | "*" => Ok(Oper::Mul),
+            "div" | "/" => Ok(Oper::Div),
+            "cat" | "||" => Ok(Oper::Cat),
+            "pow" | "**" => Ok(Oper::Pow),
+            "xor" | "^" => Ok(Oper::Xor),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Oper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sym = match self {
+            Oper::Add => "+",
+            Oper::Sub => "-",
+            Oper::Mul => "*",
+            Oper::Div => "/",
+            Oper::Cat => "||",
+            Oper::Pow => "**",
+            Oper::Xor => "^",
         };
-        for ops in repeat_n(ops, self.rhs.len() - 1).multi_cartesian_product() {
-            let mut ops_iter = ops.iter();
-            let e = self
-                .rhs
-                .iter()
-                .copied()
-                .reduce(|acc, n| match ops_iter.next() {
-                    Some(Oper::Add) => acc + n,
-                    Some(Oper::Mul) => acc * n,
-                    Some(Oper::Cat) => format!("{acc}{n}").parse::<u64>().unwrap(),
-                    _ => panic!(),
-                })
-                .unwrap();
-            if e == self.lhs {
-                return true;
+        write!(f, "{sym}")
+    }
+}
+
+impl Oper {
+    fn monotone(&self) -> bool {
+        matches!(self, Oper::Add | Oper::Mul | Oper::Cat | Oper::Pow)
+    }
+
+    fn reversible(&self) -> bool {
+        matches!(self, Oper::Add | Oper::Mul | Oper::Cat)
+    }
+}
+
+const BIG_POW_MAX_BITS: u64 = 1 << 20;
+
+trait Value: Clone + PartialOrd + From<u64> {
+    fn apply(&self, op: Oper, n: &Self) -> Option<Self>;
+}
+
+impl Value for u64 {
+    fn apply(&self, op: Oper, n: &Self) -> Option<Self> {
+        match op {
+            Oper::Add => self.checked_add(*n),
+            Oper::Sub => self.checked_sub(*n),
+            Oper::Mul => self.checked_mul(*n),
+            Oper::Div => self.checked_div(*n),
+            Oper::Cat => match 10u64.checked_pow(n.checked_ilog10().unwrap_or(0) + 1) {
+                Some(p) => self.checked_mul(p).and_then(|v| v.checked_add(*n)),
+                None => (*self == 0).then_some(*n),
+            },
+            Oper::Pow => u32::try_from(*n).ok().and_then(|e| self.checked_pow(e)),
+            Oper::Xor => Some(self ^ n),
+        }
+    }
+}
+
+impl Value for BigInt {
+    fn apply(&self, op: Oper, n: &Self) -> Option<Self> {
+        match op {
+            Oper::Add => Some(self + n),
+            Oper::Sub => Some(self - n),
+            Oper::Mul => Some(self * n),
+            Oper::Div => num::CheckedDiv::checked_div(self, n),
+            Oper::Cat if self.is_negative() || n.is_negative() => None,
+            Oper::Cat => Some(self * BigInt::from(10u32).pow(n.to_string().len() as u32) + n),
+            Oper::Pow => {
+                let e = n.to_u32()?;
+                if self.bits().saturating_mul(e as u64) > BIG_POW_MAX_BITS {
+                    None
+                } else {
+                    Some(self.pow(e))
+                }
+            }
+            Oper::Xor => Some(self ^ n),
+        }
+    }
+}
+
+fn strip_suffix(n: u64, suffix: u64) -> Option<u64> {
+    let mut p: u64 = 10;
+    while p <= suffix {
+        match p.checked_mul(10) {
+            Some(next) => p = next,
+            None => return (n == suffix).then_some(0),
+        }
+    }
+    if n % p == suffix {
+        Some(n / p)
+    } else {
+        None
+    }
+}
+
+fn min_value(rhs: &[u64], ops: &[Oper]) -> Option<u64> {
+    rhs[1..].iter().try_fold(rhs[0], |acc, n| {
+        ops.iter().filter_map(|op| acc.apply(*op, n)).min()
+    })
+}
+
+fn solve_rev(target: u64, rhs: &[u64], ops: &[Oper], dead: &mut HashSet<(usize, u64)>) -> bool {
+    let (&last, rest) = rhs.split_last().unwrap();
+    if rest.is_empty() {
+        return target == last;
+    }
+    if dead.contains(&(rest.len(), target)) {
+        return false;
+    }
+    let found = ops.iter().any(|op| match op {
+        Oper::Add => target >= last && solve_rev(target - last, rest, ops, dead),
+        Oper::Mul => {
+            if last == 0 {
+                target == 0 && min_value(rest, ops).is_some()
+            } else {
+                target.is_multiple_of(last) && solve_rev(target / last, rest, ops, dead)
+            }
+        }
+        Oper::Cat => strip_suffix(target, last).is_some_and(|t| solve_rev(t, rest, ops, dead)),
+        _ => unreachable!(),
+    });
+    if !found {
+        dead.insert((rest.len(), target));
+    }
+    found
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum CatLevel {
+    Loosest,
+    WithAdd,
+    WithMul,
+    Tightest,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Eval {
+    LeftToRight,
+    Precedence(CatLevel),
+}
+
+impl Eval {
+    fn level(&self, op: Oper) -> u8 {
+        let Eval::Precedence(cat) = self else {
+            return 0;
+        };
+        match op {
+            Oper::Cat => match cat {
+                CatLevel::Loosest => 1,
+                CatLevel::WithAdd => 4,
+                CatLevel::WithMul => 6,
+                CatLevel::Tightest => 9,
+            },
+            Oper::Xor => 2,
+            Oper::Add | Oper::Sub => 4,
+            Oper::Mul | Oper::Div => 6,
+            Oper::Pow => 8,
+        }
+    }
+
+    fn binds_before(&self, pending: Oper, next: Oper) -> bool {
+        let (a, b) = (self.level(pending), self.level(next));
+        let right_assoc = *self != Eval::LeftToRight && next == Oper::Pow;
+        a > b || (a == b && !right_assoc)
+    }
+
+    fn push<V: Value>(&self, stack: &mut Vec<(V, Oper)>, mut cur: V, op: Oper) -> Option<()> {
+        while let Some((v, top)) = stack.last() {
+            if !self.binds_before(*top, op) {
+                break;
+            }
+            cur = v.apply(*top, &cur)?;
+            stack.pop();
+        }
+        stack.push((cur, op));
+        Some(())
+    }
+
+    #[allow(dead_code)]
+    fn evaluate(&self, rhs: &[u64], ops: &[Oper]) -> Option<u64> {
+        let mut stack = Vec::new();
+        let mut cur = rhs[0];
+        for (op, n) in ops.iter().zip(&rhs[1..]) {
+            self.push(&mut stack, cur, *op)?;
+            cur = *n;
+        }
+        reduce(&stack, cur)
+    }
+}
+
+fn reduce<V: Value>(stack: &[(V, Oper)], cur: V) -> Option<V> {
+    stack
+        .iter()
+        .rev()
+        .try_fold(cur, |acc, (v, op)| v.apply(*op, &acc))
+}
+
+fn solve_fwd<V: Value>(
+    stack: Vec<(V, Oper)>,
+    cur: V,
+    target: &V,
+    rhs: &[V],
+    ops: &[Oper],
+    eval: Eval,
+    monotone: bool,
+) -> bool {
+    let Some((n, rest)) = rhs.split_first() else {
+        return reduce(&stack, cur).is_some_and(|v| v == *target);
+    };
+    if monotone && reduce(&stack, cur.clone()).is_none_or(|v| v > *target) {
+        return false;
+    }
+    ops.iter().any(|op| {
+        let mut next = stack.clone();
+        eval.push(&mut next, cur.clone(), *op).is_some()
+            && solve_fwd(next, n.clone(), target, rest, ops, eval, monotone)
+    })
+}
+
+fn count_fwd(acc: u64, target: u64, rhs: &[u64], ops: &[Oper], monotone: bool) -> usize {
+    let Some((n, rest)) = rhs.split_first() else {
+        return (acc == target) as usize;
+    };
+    if monotone && acc > target {
+        return 0;
+    }
+    ops.iter()
+        .filter_map(|op| acc.apply(*op, n))
+        .map(|v| count_fwd(v, target, rest, ops, monotone))
+        .sum()
+}
+
+struct Solutions<'a> {
+    input: &'a Input,
+    ops: &'a [Oper],
+    monotone: bool,
+    accs: Vec<u64>,
+    path: Vec<Oper>,
+    next_op: Vec<usize>,
+}
+
+impl Solutions<'_> {
+    fn backtrack(&mut self) {
+        self.accs.pop();
+        self.path.pop();
+        self.next_op.pop();
+    }
+}
+
+impl Iterator for Solutions<'_> {
+    type Item = Vec<Oper>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(&acc) = self.accs.last() {
+            let depth = self.accs.len();
+            if depth == self.input.rhs.len() {
+                let found = (acc == self.input.lhs).then(|| self.path.clone());
+                self.backtrack();
+                if found.is_some() {
+                    return found;
+                }
+                continue;
+            }
+            let k = self.next_op[depth - 1];
+            if k == self.ops.len() || (self.monotone && acc > self.input.lhs) {
+                self.backtrack();
+                continue;
+            }
+            self.next_op[depth - 1] += 1;
+            if let Some(v) = acc.apply(self.ops[k], &self.input.rhs[depth]) {
+                self.accs.push(v);
+                self.path.push(self.ops[k]);
+                self.next_op.push(0);
             }
         }
-        false
+        None
+    }
+}
+
+impl Input {
+    fn monotone(&self, ops: &[Oper]) -> bool {
+        ops.iter().all(|op| op.monotone()) && self.rhs.iter().all(|n| *n > 0)
+    }
+
+    fn is_valid(&self, ops: &[Oper]) -> bool {
+        if ops.iter().all(|op| op.reversible()) {
+            solve_rev(self.lhs, &self.rhs, ops, &mut HashSet::new())
+        } else {
+            self.is_valid_with(ops, Eval::LeftToRight)
+        }
+    }
+
+    fn is_valid_with(&self, ops: &[Oper], eval: Eval) -> bool {
+        solve_fwd(
+            Vec::new(),
+            self.rhs[0],
+            &self.lhs,
+            &self.rhs[1..],
+            ops,
+            eval,
+            self.monotone(ops),
+        )
+    }
+
+    #[allow(dead_code)]
+    fn solutions<'a>(&'a self, ops: &'a [Oper]) -> Solutions<'a> {
+        Solutions {
+            input: self,
+            ops,
+            monotone: self.monotone(ops),
+            accs: vec![self.rhs[0]],
+            path: Vec::new(),
+            next_op: vec![0],
+        }
+    }
+
+    #[allow(dead_code)]
+    fn count_solutions(&self, ops: &[Oper]) -> usize {
+        count_fwd(
+            self.rhs[0],
+            self.lhs,
+            &self.rhs[1..],
+            ops,
+            self.monotone(ops),
+        )
+    }
+
+    #[allow(dead_code)]
+    fn render(&self, ops: &[Oper]) -> String {
+        let mut out = format!("{} = {}", self.lhs, self.rhs[0]);
+        for (op, n) in ops.iter().zip(&self.rhs[1..]) {
+            out.push_str(&format!(" {op} {n}"));
+        }
+        out
+    }
+
+    #[allow(dead_code)]
+    fn is_valid_big(&self, ops: &[Oper]) -> bool {
+        Input::<BigUint>::from(self).is_valid_big(ops)
+    }
+}
+
+impl From<&Input> for Input<BigUint> {
+    fn from(input: &Input) -> Self {
+        Input {
+            lhs: BigUint::from(input.lhs),
+            rhs: input.rhs.iter().map(|n| BigUint::from(*n)).collect(),
+        }
+    }
+}
+
+impl Input<BigUint> {
+    #[allow(dead_code)]
+    fn is_valid_big(&self, ops: &[Oper]) -> bool {
+        let lhs = BigInt::from(self.lhs.clone());
+        let rhs: Vec<BigInt> = self.rhs.iter().cloned().map(BigInt::from).collect();
+        let monotone = ops.iter().all(|op| op.monotone()) && rhs.iter().all(|n| n.is_positive());
+        solve_fwd(
+            Vec::new(),
+            rhs[0].clone(),
+            &lhs,
+            &rhs[1..],
+            ops,
+            Eval::LeftToRight,
+            monotone,
+        )
     }
 }
 
 fn part1(input: &[Input]) -> u64 {
     input
         .iter()
-        .filter(|i| i.is_valid(false))
+        .filter(|i| i.is_valid(&[Oper::Add, Oper::Mul]))
         .map(|i| i.lhs)
         .sum()
 }
@@ -65,7 +426,7 @@ fn part1(input: &[Input]) -> u64 {
 fn part2(input: &[Input]) -> u64 {
     input
         .iter()
-        .filter(|i| i.is_valid(true))
+        .filter(|i| i.is_valid(&[Oper::Add, Oper::Mul, Oper::Cat]))
         .map(|i| i.lhs)
         .sum()
 }
@@ -85,12 +446,13 @@ fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use itertools::Itertools;
     use ya_advent_lib::read::test_input;
 
-    #[test]
-    fn day07_test() {
-        let input: Vec<Input> = test_input(
-            "190: 10 19
+    const P1: &[Oper] = &[Oper::Add, Oper::Mul];
+    const P2: &[Oper] = &[Oper::Add, Oper::Mul, Oper::Cat];
+
+    const EXAMPLE: &str = "190: 10 19
 3267: 81 40 27
 83: 17 5
 156: 15 6
@@ -99,9 +461,194 @@ mod tests {
 192: 17 8 14
 21037: 9 7 18 13
 292: 11 6 16 20
-",
-        );
+";
+
+    #[test]
+    fn day07_test() {
+        let input: Vec<Input> = test_input(EXAMPLE);
         assert_eq!(part1(&input), 3749);
         assert_eq!(part2(&input), 11387);
     }
+
+    #[test]
+    fn day07_reverse_test() {
+        let input: Vec<Input> = test_input(
+            "0: 5 0
+10: 10 0 0
+10: 1 0
+1230: 12 3 0
+18446744073709551615: 18446744073709551 615
+",
+        );
+        assert!(input[0].is_valid(P1));
+        assert!(input[1].is_valid(P1));
+        assert!(input[2].is_valid(P2));
+        assert!(!input[2].is_valid(P1));
+        assert!(input[3].is_valid(P2));
+        assert!(input[4].is_valid(P2));
+        assert!(!input[4].is_valid(P1));
+        let long = Input {
+            lhs: 5040,
+            rhs: (1..=7).chain(std::iter::repeat_n(1, 200)).collect(),
+        };
+        assert!(long.is_valid(P1));
+        assert!(long.is_valid(P2));
+        let long = Input {
+            lhs: 5041,
+            rhs: (1..=7).chain(std::iter::repeat_n(2, 200)).collect(),
+        };
+        assert!(!long.is_valid(P2));
+
+        let zero = Input {
+            lhs: 0,
+            rhs: vec![u64::MAX, 2, 0],
+        };
+        assert!(!zero.is_valid(P1));
+        assert!(!zero.is_valid_with(P1, Eval::LeftToRight));
+        let zero = Input {
+            lhs: 0,
+            rhs: vec![u64::MAX, 0, 2, 0],
+        };
+        assert!(zero.is_valid(P1));
+        assert!(zero.is_valid_with(P1, Eval::LeftToRight));
+    }
+
+    #[test]
+    fn day07_operators_test() {
+        assert_eq!("**".parse::<Oper>(), Ok(Oper::Pow));
+        assert_eq!("xor".parse::<Oper>(), Ok(Oper::Xor));
+        assert_eq!("mod".parse::<Oper>(), Err(()));
+        let input: Vec<Input> = test_input(
+            "2: 10 3 5
+3: 20 6
+64: 2 3 2
+6: 5 3
+6: 18446744073709551615 6 18446744073709551615
+1: 18446744073709551615 9
+",
+        );
+        assert!(input[0].is_valid(&[Oper::Sub]));
+        assert!(input[1].is_valid(&[Oper::Div]));
+        assert!(input[2].is_valid(&[Oper::Pow]));
+        assert!(!input[2].is_valid(&[Oper::Add, Oper::Mul]));
+        assert!(input[3].is_valid(&[Oper::Xor]));
+        assert!(!input[4].is_valid(&[Oper::Mul, Oper::Div]));
+        assert!(input[4].is_valid_big(&[Oper::Mul, Oper::Div]));
+        assert!(!input[5].is_valid(&[Oper::Cat]));
+        assert!(!input[5].is_valid(&[Oper::Cat, Oper::Pow]));
+        assert!(!input[5].is_valid_big(&[Oper::Cat, Oper::Pow]));
+        let cat = Input {
+            lhs: u64::MAX,
+            rhs: vec![0, u64::MAX],
+        };
+        assert!(cat.is_valid(&[Oper::Cat]));
+        assert!(cat.is_valid(&[Oper::Cat, Oper::Sub]));
+        let big: Vec<Input<BigUint>> = test_input(
+            "18446744073709551616: 18446744073709551615 1
+340282366920938463463374607431768211456: 18446744073709551616 18446744073709551616
+1000000000000000000000: 10000000000 100000000000
+10000000000100000000000: 10000000000 100000000000
+",
+        );
+        assert!(big[0].is_valid_big(&[Oper::Add]));
+        assert!(!big[0].is_valid_big(&[Oper::Mul]));
+        assert!(big[1].is_valid_big(&[Oper::Add, Oper::Mul]));
+        assert!(big[2].is_valid_big(&[Oper::Mul]));
+        assert!(!big[2].is_valid_big(&[Oper::Cat]));
+        assert!(big[3].is_valid_big(&[Oper::Add, Oper::Cat]));
+        let neg: Vec<Input> = test_input("15: 5 10 20\n7: 1 2 8\n");
+        assert!(!neg[0].is_valid(&[Oper::Sub, Oper::Add]));
+        assert!(neg[0].is_valid_big(&[Oper::Sub, Oper::Add]));
+        assert!(!neg[1].is_valid_big(&[Oper::Sub, Oper::Cat]));
+        assert!(neg[1].is_valid_big(&[Oper::Sub, Oper::Add]));
+        assert!("18446744073709551616: 1 1".parse::<Input>().is_err());
+        assert!("5: 1 18446744073709551616".parse::<Input>().is_err());
+        assert!(input.iter().take(4).all(|i| i.is_valid_big(&[
+            Oper::Sub,
+            Oper::Div,
+            Oper::Pow,
+            Oper::Xor
+        ])));
+    }
+
+    #[test]
+    fn day07_solutions_test() {
+        let input: Vec<Input> = test_input(EXAMPLE);
+        let rendered: Vec<String> = input[1]
+            .solutions(P1)
+            .map(|ops| input[1].render(&ops))
+            .collect();
+        assert_eq!(rendered, vec!["3267 = 81 + 40 * 27", "3267 = 81 * 40 + 27"]);
+        assert_eq!(input[1].count_solutions(P1), 2);
+        assert_eq!(
+            input[8].solutions(P1).next(),
+            Some(vec![Oper::Add, Oper::Mul, Oper::Add])
+        );
+        assert_eq!(
+            input[8].render(&[Oper::Add, Oper::Mul, Oper::Add]),
+            "292 = 11 + 6 * 16 + 20"
+        );
+        assert_eq!(input[5].solutions(P1).next(), None);
+        assert_eq!(
+            input
+                .iter()
+                .map(|i| i.count_solutions(P2))
+                .collect::<Vec<_>>(),
+            input
+                .iter()
+                .map(|i| i.solutions(P2).count())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            input[6]
+                .solutions(P2)
+                .map(|ops| input[6].render(&ops))
+                .collect::<Vec<_>>(),
+            vec!["192 = 17 || 8 + 14"]
+        );
+    }
+
+    #[test]
+    fn day07_precedence_test() {
+        let prec = Eval::Precedence(CatLevel::Tightest);
+        let add_mul = [Oper::Add, Oper::Mul];
+        assert_eq!(prec.evaluate(&[2, 3, 4], &add_mul), Some(14));
+        assert_eq!(Eval::LeftToRight.evaluate(&[2, 3, 4], &add_mul), Some(20));
+        assert_eq!(
+            prec.evaluate(&[2, 3, 2], &[Oper::Pow, Oper::Pow]),
+            Some(512)
+        );
+        assert_eq!(
+            Eval::LeftToRight.evaluate(&[2, 3, 2], &[Oper::Pow, Oper::Pow]),
+            Some(64)
+        );
+        let add_cat = [Oper::Add, Oper::Cat];
+        let cat_mul = [Oper::Cat, Oper::Mul];
+        assert_eq!(prec.evaluate(&[1, 2, 3], &add_cat), Some(24));
+        assert_eq!(prec.evaluate(&[1, 2, 3], &cat_mul), Some(36));
+        let loosest = Eval::Precedence(CatLevel::Loosest);
+        assert_eq!(loosest.evaluate(&[1, 2, 3], &add_cat), Some(33));
+        assert_eq!(loosest.evaluate(&[1, 2, 3], &cat_mul), Some(16));
+        let with_add = Eval::Precedence(CatLevel::WithAdd);
+        assert_eq!(with_add.evaluate(&[1, 2, 3], &add_cat), Some(33));
+        assert_eq!(with_add.evaluate(&[1, 2, 3], &cat_mul), Some(16));
+        let with_mul = Eval::Precedence(CatLevel::WithMul);
+        assert_eq!(with_mul.evaluate(&[1, 2, 3], &add_cat), Some(24));
+        assert_eq!(with_mul.evaluate(&[1, 2, 3], &cat_mul), Some(36));
+
+        let input: Vec<Input> = test_input("14: 2 3 4\n3267: 81 40 27\n");
+        assert!(input[0].is_valid_with(P1, prec));
+        assert!(!input[0].is_valid(P1));
+        assert!(input[1].is_valid_with(P1, prec));
+
+        let input: Vec<Input> = test_input(EXAMPLE);
+        for eval in [prec, loosest, with_add, with_mul] {
+            for i in &input {
+                let brute = std::iter::repeat_n(P2.iter().copied(), i.rhs.len() - 1)
+                    .multi_cartesian_product()
+                    .any(|ops| eval.evaluate(&i.rhs, &ops) == Some(i.lhs));
+                assert_eq!(i.is_valid_with(P2, eval), brute);
+            }
+        }
+    }
 }
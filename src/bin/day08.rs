@@ -1,5 +1,7 @@
 use itertools::Itertools;
 use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::ops::{Add, Neg, Sub};
 use std::time::Instant;
 use std::vec::Vec;
 use ya_advent_lib::coords::Coord2D;
@@ -21,10 +23,171 @@ impl From<char> for Cell {
     }
 }
 
-fn bothparts(input: &[String]) -> (usize, usize) {
-    let grid: Grid<Cell> = Grid::from_input(input, Cell::Empty, 0);
-    let mut antinodes: HashSet<Coord2D> = HashSet::new();
-    let mut antinodes2: HashSet<Coord2D> = HashSet::new();
+#[derive(Copy, Clone)]
+enum Rule {
+    Beyond(i64, i64),
+    Between(i64, i64),
+    Collinear,
+}
+
+const PART1: &[Rule] = &[Rule::Beyond(2, 1), Rule::Between(2, 1)];
+const PART2: &[Rule] = &[Rule::Collinear];
+
+trait Point: Copy + Eq + Hash + Add<Output = Self> + Sub<Output = Self> + Neg<Output = Self> {
+    fn components(&self) -> Vec<i64>;
+    fn map(&self, f: impl Fn(i64) -> i64) -> Self;
+}
+
+impl Point for Coord2D {
+    fn components(&self) -> Vec<i64> {
+        vec![self.x, self.y]
+    }
+
+    fn map(&self, f: impl Fn(i64) -> i64) -> Self {
+        Coord2D::new(f(self.x), f(self.y))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Coord3D {
+    x: i64,
+    y: i64,
+    z: i64,
+}
+
+impl Coord3D {
+    fn new(x: i64, y: i64, z: i64) -> Self {
+        Coord3D { x, y, z }
+    }
+}
+
+impl Add for Coord3D {
+    type Output = Self;
+    fn add(self, o: Self) -> Self {
+        Coord3D::new(self.x + o.x, self.y + o.y, self.z + o.z)
+    }
+}
+
+impl Sub for Coord3D {
+    type Output = Self;
+    fn sub(self, o: Self) -> Self {
+        Coord3D::new(self.x - o.x, self.y - o.y, self.z - o.z)
+    }
+}
+
+impl Neg for Coord3D {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Coord3D::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl Point for Coord3D {
+    fn components(&self) -> Vec<i64> {
+        vec![self.x, self.y, self.z]
+    }
+
+    fn map(&self, f: impl Fn(i64) -> i64) -> Self {
+        Coord3D::new(f(self.x), f(self.y), f(self.z))
+    }
+}
+
+fn scaled<P: Point>(a: P, diff: P, k: i64, q: i64) -> Option<P> {
+    (q != 0 && diff.components().iter().all(|v| (v * k) % q == 0))
+        .then(|| a + diff.map(|v| v * k / q))
+}
+
+impl Rule {
+    fn antinodes<P: Point>(&self, a: P, b: P, inside: impl Fn(P) -> bool) -> Vec<P> {
+        let diff = b - a;
+        let fractions = match *self {
+            Rule::Beyond(m, n) => [(m, m - n), (n, n - m)],
+            Rule::Between(m, n) => [(m, m + n), (n, m + n)],
+            Rule::Collinear => {
+                let g = diff.components().into_iter().fold(0, num::integer::gcd);
+                let step = diff.map(|v| v / g);
+                let mut out = Vec::new();
+                for dir in [step, -step] {
+                    let mut p = if dir == step { a } else { a + dir };
+                    while inside(p) {
+                        out.push(p);
+                        p = p + dir;
+                    }
+                }
+                return out;
+            }
+        };
+        fractions
+            .into_iter()
+            .filter_map(|(k, q)| scaled(a, diff, k, q))
+            .filter(|p| inside(*p))
+            .collect()
+    }
+}
+
+#[allow(dead_code)]
+struct Field3D {
+    size: Coord3D,
+    antennas: HashMap<char, Vec<Coord3D>>,
+}
+
+#[allow(dead_code)]
+impl Field3D {
+    fn parse(input: &[String]) -> Self {
+        let mut antennas: HashMap<char, Vec<Coord3D>> = HashMap::new();
+        let mut size = Coord3D::new(0, 0, 0);
+        for (z, layer) in input.split(|l| l.is_empty()).enumerate() {
+            size.z = z as i64 + 1;
+            size.y = size.y.max(layer.len() as i64);
+            for (y, line) in layer.iter().enumerate() {
+                size.x = size.x.max(line.len() as i64);
+                for (x, c) in line.chars().enumerate() {
+                    if let Cell::Antenna(f) = Cell::from(c) {
+                        antennas
+                            .entry(f)
+                            .or_default()
+                            .push(Coord3D::new(x as i64, y as i64, z as i64));
+                    }
+                }
+            }
+        }
+        Field3D { size, antennas }
+    }
+
+    fn contains(&self, c: Coord3D) -> bool {
+        (0..self.size.x).contains(&c.x)
+            && (0..self.size.y).contains(&c.y)
+            && (0..self.size.z).contains(&c.z)
+    }
+
+    fn antinodes(&self, rules: &[Rule]) -> HashSet<Coord3D> {
+        resonate(&self.antennas, rules, |c| self.contains(c))
+            .into_values()
+            .flatten()
+            .collect()
+    }
+}
+
+fn resonate<P: Point>(
+    by_freq: &HashMap<char, Vec<P>>,
+    rules: &[Rule],
+    inside: impl Fn(P) -> bool,
+) -> HashMap<char, HashSet<P>> {
+    by_freq
+        .iter()
+        .map(|(freq, lst)| {
+            let mut antinodes: HashSet<P> = HashSet::new();
+            lst.iter().tuple_combinations().for_each(|(&a, &b)| {
+                for rule in rules {
+                    antinodes.extend(rule.antinodes(a, b, &inside));
+                }
+            });
+            (*freq, antinodes)
+        })
+        .collect()
+}
+
+fn by_freq(grid: &Grid<Cell>) -> HashMap<char, Vec<Coord2D>> {
     let mut by_freq: HashMap<char, Vec<Coord2D>> = HashMap::new();
     grid.iter_with_coord()
         .filter_map(|(cell, x, y)| match cell {
@@ -38,36 +201,71 @@ fn bothparts(input: &[String]) -> (usize, usize) {
                 .or_insert(Vec::from_iter([(x, y).into()]));
         });
     by_freq
+}
+
+fn freq_antinodes(grid: &Grid<Cell>, rules: &[Rule]) -> HashMap<char, HashSet<Coord2D>> {
+    resonate(&by_freq(grid), rules, |c| grid.contains_coord(c))
+}
+
+fn antinodes(grid: &Grid<Cell>, rules: &[Rule]) -> HashSet<Coord2D> {
+    freq_antinodes(grid, rules)
         .into_values()
-        .filter(|lst| lst.len() > 1)
-        .for_each(|lst| {
-            lst.iter().tuple_combinations().for_each(|(&a, &b)| {
-                antinodes2.insert(a);
-                antinodes2.insert(b);
-                let diff = b - a;
-                let mut n1 = a - diff;
-                let mut first = true;
-                while grid.contains_coord(n1) {
-                    if first {
-                        antinodes.insert(n1);
-                        first = false;
-                    }
-                    antinodes2.insert(n1);
-                    n1 -= diff;
-                }
-                let mut n2 = b + diff;
-                first = true;
-                while grid.contains_coord(n2) {
-                    if first {
-                        antinodes.insert(n2);
-                        first = false;
-                    }
-                    antinodes2.insert(n2);
-                    n2 += diff;
-                }
-            });
-        });
-    (antinodes.len(), antinodes2.len())
+        .flatten()
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+struct FreqReport {
+    freq: char,
+    antennas: usize,
+    antinodes: usize,
+    shared: usize,
+}
+
+#[allow(dead_code)]
+fn report(grid: &Grid<Cell>, rules: &[Rule]) -> Vec<FreqReport> {
+    let antennas = by_freq(grid);
+    let per_freq = freq_antinodes(grid, rules);
+    let mut out: Vec<FreqReport> = per_freq
+        .iter()
+        .map(|(freq, nodes)| FreqReport {
+            freq: *freq,
+            antennas: antennas[freq].len(),
+            antinodes: nodes.len(),
+            shared: nodes
+                .iter()
+                .filter(|c| {
+                    per_freq
+                        .iter()
+                        .any(|(other, o)| other != freq && o.contains(c))
+                })
+                .count(),
+        })
+        .collect();
+    out.sort_by_key(|r| r.freq);
+    out
+}
+
+#[allow(dead_code)]
+fn render(grid: &Grid<Cell>, rules: &[Rule]) -> String {
+    let nodes = antinodes(grid, rules);
+    grid.y_bounds_orig()
+        .map(|y| {
+            grid.x_bounds_orig()
+                .map(|x| match grid.get(x, y) {
+                    Cell::Antenna(c) => c,
+                    Cell::Empty if nodes.contains(&Coord2D::new(x, y)) => '#',
+                    Cell::Empty => '.',
+                })
+                .chain(['\n'])
+                .collect::<String>()
+        })
+        .collect()
+}
+
+fn bothparts(input: &[String]) -> (usize, usize) {
+    let grid: Grid<Cell> = Grid::from_input(input, Cell::Empty, 0);
+    (antinodes(&grid, PART1).len(), antinodes(&grid, PART2).len())
 }
 
 fn main() {
@@ -85,10 +283,7 @@ mod tests {
     use super::*;
     use ya_advent_lib::read::test_input;
 
-    #[test]
-    fn day08_test() {
-        let input: Vec<String> = test_input(
-            "............
+    const EXAMPLE: &str = "............
 ........0...
 .....0......
 .......0....
@@ -100,10 +295,113 @@ mod tests {
 .........A..
 ............
 ............
-",
-        );
+";
+
+    #[test]
+    fn day08_test() {
+        let input: Vec<String> = test_input(EXAMPLE);
         let (part1, part2) = bothparts(&input);
         assert_eq!(part1, 14);
         assert_eq!(part2, 34);
     }
+
+    #[test]
+    fn day08_rules_test() {
+        let grid: Grid<Cell> = Grid::from_input(&test_input("a..a......"), Cell::Empty, 0);
+        assert_eq!(antinodes(&grid, PART1).len(), 3);
+        assert_eq!(antinodes(&grid, &[Rule::Beyond(2, 1)]).len(), 1);
+        assert_eq!(antinodes(&grid, &[Rule::Between(1, 1)]).len(), 0);
+        let grid: Grid<Cell> = Grid::from_input(&test_input("a.a......."), Cell::Empty, 0);
+        assert_eq!(antinodes(&grid, &[Rule::Beyond(3, 1)]).len(), 1);
+        assert_eq!(antinodes(&grid, &[Rule::Between(1, 1)]).len(), 1);
+        let grid: Grid<Cell> = Grid::from_input(
+            &test_input("a....\n.....\n..a..\n.....\n.....\n"),
+            Cell::Empty,
+            0,
+        );
+        assert_eq!(antinodes(&grid, PART2).len(), 5);
+    }
+
+    #[test]
+    fn day08_report_test() {
+        let grid: Grid<Cell> = Grid::from_input(&test_input(EXAMPLE), Cell::Empty, 0);
+        assert_eq!(
+            render(&grid, PART1),
+            "......#....#
+...#....0...
+....#0....#.
+..#....0....
+....0....#..
+.#....A.....
+...#........
+#......#....
+........A...
+.........A..
+..........#.
+..........#.
+"
+        );
+        assert_eq!(
+            report(&grid, PART1),
+            vec![
+                FreqReport {
+                    freq: '0',
+                    antennas: 4,
+                    antinodes: 10,
+                    shared: 1,
+                },
+                FreqReport {
+                    freq: 'A',
+                    antennas: 3,
+                    antinodes: 5,
+                    shared: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn day08_3d_test() {
+        let field = Field3D::parse(&test_input(EXAMPLE));
+        assert_eq!(field.size, Coord3D::new(12, 12, 1));
+        assert_eq!(field.antinodes(PART1).len(), 14);
+        assert_eq!(field.antinodes(PART2).len(), 34);
+        let field = Field3D::parse(&test_input(
+            "a...
+....
+....
+....
+
+....
+.a..
+....
+....
+
+....
+....
+....
+....
+
+....
+....
+....
+....
+",
+        ));
+        assert_eq!(field.size, Coord3D::new(4, 4, 4));
+        assert_eq!(
+            field.antinodes(PART1),
+            HashSet::from_iter([Coord3D::new(2, 2, 2)])
+        );
+        assert_eq!(field.antinodes(PART2).len(), 4);
+        let field = Field3D {
+            size: Coord3D::new(4, 4, 4),
+            antennas: HashMap::from_iter([(
+                'a',
+                vec![Coord3D::new(0, 0, 0), Coord3D::new(2, 2, 2)],
+            )]),
+        };
+        assert!(field.antinodes(PART1).is_empty());
+        assert_eq!(field.antinodes(PART2).len(), 4);
+    }
 }
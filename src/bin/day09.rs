@@ -1,64 +1,291 @@
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::fmt;
 use std::iter::repeat_n;
 use std::str::FromStr;
 use std::time::Instant;
 use std::vec::Vec;
 use ya_advent_lib::read::read_input;
 
-type FileId = usize;
+type FileId = u64;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Blocks {
-    File(FileId, usize),
-    Empty(usize),
+    File(FileId, u64),
+    Empty(u64),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 struct DiskMap(Vec<Blocks>);
 
+#[derive(Debug, PartialEq)]
+struct ParseError {
+    pos: usize,
+    found: char,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected digit at {}, found '{}'",
+            self.pos + 1,
+            self.found
+        )
+    }
+}
+
 impl FromStr for DiskMap {
-    type Err = ();
+    type Err = ParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let pad = if s.len() & 1 == 1 {
-            vec![0usize]
+        let digits = s
+            .chars()
+            .enumerate()
+            .map(|(pos, c)| {
+                c.to_digit(10)
+                    .map(u64::from)
+                    .ok_or(ParseError { pos, found: c })
+            })
+            .collect::<Result<Vec<u64>, _>>()?;
+        let pad = if digits.len() & 1 == 1 {
+            vec![0u64]
         } else {
             vec![]
         };
-        let dmap = s
-            .chars()
-            .map(|c| c as usize - '0' as usize)
+        let dmap = digits
+            .into_iter()
             .chain(pad)
             .tuples::<(_, _)>()
             .enumerate()
-            .flat_map(|(idx, (d, e))| [Blocks::File(idx, d), Blocks::Empty(e)])
+            .flat_map(|(idx, (d, e))| [Blocks::File(idx as FileId, d), Blocks::Empty(e)])
             .filter(|b| !matches!(b, Blocks::Empty(0)))
             .collect();
         Ok(DiskMap(dmap))
     }
 }
 
+impl fmt::Display for DiskMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in &self.0 {
+            let (c, n) = match *b {
+                Blocks::File(id, n) => (char::from_digit((id % 10) as u32, 10).unwrap(), n),
+                Blocks::Empty(n) => ('.', n),
+            };
+            match f.precision() {
+                Some(max) if n > max as u64 => write!(f, "{c}{{{n}}}")?,
+                _ => {
+                    for _ in 0..n {
+                        write!(f, "{c}")?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 impl DiskMap {
-    fn checksum(&self) -> usize {
+    fn checksum(&self) -> u128 {
+        let mut pos = 0;
+        let mut sum = 0;
+        for b in &self.0 {
+            match *b {
+                Blocks::File(id, n) => {
+                    let (id, n, p) = (id as u128, n as u128, pos as u128);
+                    sum += id * (n * p + n * n.saturating_sub(1) / 2);
+                    pos += n as u64;
+                }
+                Blocks::Empty(n) => {
+                    pos += n;
+                }
+            }
+        }
+        sum
+    }
+
+    #[allow(dead_code)]
+    fn to_dense(&self) -> String {
+        let mut runs: Vec<Blocks> = Vec::new();
+        for b in &self.0 {
+            match (runs.last_mut(), *b) {
+                (_, Blocks::Empty(0)) => {}
+                (Some(Blocks::File(last, len)), Blocks::File(id, n)) if *last == id => *len += n,
+                (Some(Blocks::Empty(len)), Blocks::Empty(n)) => *len += n,
+                (_, b) => runs.push(b),
+            }
+        }
+        let mut digits: Vec<u64> = Vec::new();
+        for b in runs {
+            let (mut n, parity) = match b {
+                Blocks::File(_, n) => (n, 0),
+                Blocks::Empty(n) => (n, 1),
+            };
+            if digits.len() % 2 != parity {
+                digits.push(0);
+            }
+            loop {
+                let d = n.min(9);
+                digits.push(d);
+                n -= d;
+                if n == 0 {
+                    break;
+                }
+                digits.push(0);
+            }
+        }
+        digits
+            .into_iter()
+            .map(|d| char::from_digit(d as u32, 10).unwrap())
+            .collect()
+    }
+
+    fn starts(&self) -> Vec<u64> {
         self.0
             .iter()
-            .flat_map(|b| match b {
-                Blocks::File(id, n) => repeat_n(Blocks::File(*id, 1), *n),
-                Blocks::Empty(n) => repeat_n(Blocks::Empty(1), *n),
+            .scan(0, |pos, b| {
+                let start = *pos;
+                *pos += match *b {
+                    Blocks::File(_, n) | Blocks::Empty(n) => n,
+                };
+                Some(start)
             })
-            .enumerate()
-            .filter_map(|(idx, b)| match b {
-                Blocks::File(bid, _) => Some((idx, bid)),
-                _ => None,
+            .collect()
+    }
+
+    fn from_blocks(blocks: &[Option<FileId>]) -> Self {
+        DiskMap(
+            blocks
+                .iter()
+                .chunk_by(|b| **b)
+                .into_iter()
+                .map(|(b, run)| {
+                    let n = run.count() as u64;
+                    match b {
+                        Some(id) => Blocks::File(id, n),
+                        None => Blocks::Empty(n),
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    #[allow(dead_code)]
+    fn trace(&self, strategy: &impl DefragStrategy) -> Vec<DiskMap> {
+        let mut blocks: Vec<Option<FileId>> = self
+            .0
+            .iter()
+            .flat_map(|b| match *b {
+                Blocks::File(id, n) => repeat_n(Some(id), n as usize),
+                Blocks::Empty(n) => repeat_n(None, n as usize),
+            })
+            .collect();
+        let mut layouts = vec![DiskMap::from_blocks(&blocks)];
+        for mv in strategy.defrag(&mut self.clone()) {
+            let steps: Vec<(u64, u64, u64)> = if strategy.blockwise() {
+                (0..mv.len)
+                    .map(|k| (mv.from + mv.len - 1 - k, mv.to + k, 1))
+                    .collect()
+            } else {
+                vec![(mv.from, mv.to, mv.len)]
+            };
+            for (from, to, len) in steps {
+                let (from, to, len) = (from as usize, to as usize, len as usize);
+                let moving: Vec<Option<FileId>> = blocks[from..from + len].to_vec();
+                blocks[from..from + len].fill(None);
+                blocks[to..to + len].copy_from_slice(&moving);
+                layouts.push(DiskMap::from_blocks(&blocks));
+            }
+        }
+        layouts
+    }
+
+    fn file_runs(&self) -> Vec<(u64, u64, FileId)> {
+        let mut runs: Vec<(u64, u64, FileId)> = Vec::new();
+        let mut pos = 0;
+        for b in &self.0 {
+            match *b {
+                Blocks::File(_, 0) => {}
+                Blocks::File(id, n) => match runs.last_mut() {
+                    Some((_, end, last)) if *end == pos && *last == id => *end += n,
+                    _ => runs.push((pos, pos + n, id)),
+                },
+                Blocks::Empty(_) => {}
+            }
+            pos += match *b {
+                Blocks::File(_, n) | Blocks::Empty(n) => n,
+            };
+        }
+        runs
+    }
+
+    fn metrics(&self, before: &DiskMap) -> DefragMetrics {
+        let runs = self.file_runs();
+        let mut by_id: HashMap<FileId, Vec<(u64, u64)>> = HashMap::new();
+        for (start, end, id) in before.file_runs() {
+            by_id.entry(id).or_default().push((start, end));
+        }
+        let len: u64 = before
+            .0
+            .iter()
+            .map(|b| match *b {
+                Blocks::File(_, n) | Blocks::Empty(n) => n,
             })
-            .map(|(idx, bid)| idx * bid)
-            .sum()
+            .sum();
+        let gaps: Vec<u64> = [(0, 0, 0)]
+            .iter()
+            .chain(&runs)
+            .chain(&[(len, len, 0)])
+            .tuple_windows()
+            .map(|((_, end, _), (start, _, _))| start - end)
+            .filter(|gap| *gap > 0)
+            .collect();
+        DefragMetrics {
+            fragmented_files: runs
+                .iter()
+                .counts_by(|(_, _, id)| id)
+                .values()
+                .filter(|n| **n > 1)
+                .count(),
+            free_spans: gaps.len(),
+            largest_free_span: gaps.iter().max().copied().unwrap_or(0),
+            bytes_moved: runs
+                .iter()
+                .map(|(start, end, id)| {
+                    let kept: u64 = by_id
+                        .get(id)
+                        .into_iter()
+                        .flatten()
+                        .map(|(s, e)| e.min(end).saturating_sub(*s.max(start)))
+                        .sum();
+                    end - start - kept
+                })
+                .sum(),
+        }
+    }
+
+    #[allow(dead_code)]
+    fn defrag(&mut self, strategy: &impl DefragStrategy) -> DefragMetrics {
+        let before = self.clone();
+        strategy.defrag(self);
+        self.metrics(&before)
     }
-    fn compact(&mut self) {
+
+    fn compact(&mut self) -> Vec<Move> {
+        if !self
+            .0
+            .iter()
+            .skip_while(|b| matches!(b, Blocks::File(..)))
+            .any(|b| matches!(b, Blocks::File(..)))
+        {
+            return Vec::new();
+        }
+        let starts = self.starts();
+        let mut moved: Vec<(usize, u64)> = Vec::new();
         let mut out = Vec::new();
         let mut fwd_idx = 0usize;
         let mut rev_idx = self.0.len() - 1;
-        let mut by_id: HashMap<FileId, usize> = HashMap::new();
+        let mut by_id: HashMap<FileId, u64> = HashMap::new();
         while matches!(self.0[rev_idx], Blocks::Empty(_)) {
             rev_idx -= 1;
         }
@@ -75,6 +302,7 @@ impl DiskMap {
         };
         'outer: while fwd_idx < rev_idx {
             if end_blk_len <= cur_empty_space {
+                moved.push((out.len(), starts[rev_idx]));
                 out.push(Blocks::File(end_blk_id, end_blk_len));
                 by_id
                     .entry(end_blk_id)
@@ -82,9 +310,13 @@ impl DiskMap {
                     .or_insert(end_blk_len);
                 cur_empty_space -= end_blk_len;
                 rev_idx -= 1;
-                while matches!(self.0[rev_idx], Blocks::Empty(_)) {
+                while rev_idx > fwd_idx && matches!(self.0[rev_idx], Blocks::Empty(_)) {
                     rev_idx -= 1;
                 }
+                if rev_idx <= fwd_idx {
+                    end_blk_len = 0;
+                    break;
+                }
                 let Blocks::File(nxt_blk_id, nxt_blk_len) = self.0[rev_idx] else {
                     panic!();
                 };
@@ -92,6 +324,7 @@ impl DiskMap {
                 end_blk_len = nxt_blk_len;
             } else {
                 end_blk_len -= cur_empty_space;
+                moved.push((out.len(), starts[rev_idx] + end_blk_len));
                 out.push(Blocks::File(end_blk_id, cur_empty_space));
                 by_id
                     .entry(end_blk_id)
@@ -118,72 +351,224 @@ impl DiskMap {
                 cur_empty_space = next_empty_space;
             }
         }
-        let Blocks::File(_, bsz) = self.0[rev_idx] else {
-            panic!();
-        };
-        if end_blk_len > 0 && (!by_id.contains_key(&end_blk_id) || by_id[&end_blk_id] < bsz) {
-            let len = by_id.get(&end_blk_id).copied().unwrap_or(0);
-            out.push(Blocks::File(end_blk_id, bsz - len));
+        if let Blocks::File(_, bsz) = self.0[rev_idx] {
+            if end_blk_len > 0 && (!by_id.contains_key(&end_blk_id) || by_id[&end_blk_id] < bsz) {
+                let len = by_id.get(&end_blk_id).copied().unwrap_or(0);
+                moved.push((out.len(), starts[rev_idx]));
+                out.push(Blocks::File(end_blk_id, bsz - len));
+            }
         }
 
         self.0 = out;
-    }
-
-    fn compact2(&mut self) {
-        let mut file_id = self
-            .0
-            .iter()
-            .rev()
-            .flat_map(|b| match b {
-                Blocks::File(id, _) => Some(*id),
+        let new_starts = self.starts();
+        moved
+            .into_iter()
+            .filter_map(|(idx, from)| match self.0[idx] {
+                Blocks::File(id, len) if new_starts[idx] != from => Some(Move {
+                    id,
+                    from,
+                    to: new_starts[idx],
+                    len,
+                }),
                 _ => None,
             })
-            .next()
-            .unwrap();
-        while file_id > 0 {
-            let Some(b_idx) = self
-                .0
-                .iter()
-                .position(|b| matches!(b, Blocks::File(id, _) if *id == file_id))
-            else {
-                panic!();
-            };
-            let Blocks::File(_, bsz) = self.0[b_idx] else {
-                panic!();
-            };
+            .collect()
+    }
 
-            if let Some(e_idx) = self
-                .0
-                .iter()
-                .position(|b| matches!(b, Blocks::Empty(n) if *n >= bsz))
-            {
-                if e_idx < b_idx {
-                    self.0[b_idx] = Blocks::Empty(bsz);
-                    let mut r = vec![Blocks::File(file_id, bsz)];
-                    let Blocks::Empty(esz) = self.0[e_idx] else {
-                        panic!();
-                    };
-                    if bsz < esz {
-                        r.push(Blocks::Empty(esz - bsz));
+    fn move_files(&mut self, fit: Fit) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let mut files: Vec<(u64, FileId, u64)> = Vec::new();
+        let mut index: HashMap<FileId, usize> = HashMap::new();
+        let mut spans: BTreeMap<u64, u64> = BTreeMap::new();
+        let mut pos = 0;
+        for b in &self.0 {
+            match *b {
+                Blocks::File(id, n) => {
+                    index.entry(id).or_insert(files.len());
+                    files.push((pos, id, n));
+                    pos += n;
+                }
+                Blocks::Empty(0) => {}
+                Blocks::Empty(n) => {
+                    match spans.last_key_value() {
+                        Some((&start, &len)) if start + len == pos => {
+                            spans.insert(start, len + n);
+                        }
+                        _ => {
+                            spans.insert(pos, n);
+                        }
                     }
-                    self.0.splice(e_idx..e_idx + 1, r);
+                    pos += n;
                 }
             }
+        }
+        let disk_len = pos;
+        let mut free: BTreeMap<u64, BinaryHeap<Reverse<u64>>> = BTreeMap::new();
+        for (pos, n) in &spans {
+            free.entry(*n).or_default().push(Reverse(*pos));
+        }
 
-            file_id -= 1;
+        let mut ids: Vec<FileId> = index.keys().copied().collect();
+        ids.sort_unstable();
+        for id in ids.into_iter().rev() {
+            let f = index[&id];
+            let (fpos, _, n) = files[f];
+            if n == 0 {
+                continue;
+            }
+            free.retain(|sz, heap| {
+                while heap
+                    .peek()
+                    .is_some_and(|Reverse(p)| spans.get(p) != Some(sz))
+                {
+                    heap.pop();
+                }
+                !heap.is_empty()
+            });
+            let fits: Vec<(u64, u64)> = free
+                .range(n..)
+                .filter_map(|(sz, heap)| heap.peek().map(|Reverse(p)| (*p, *sz)))
+                .filter(|(p, _)| *p < fpos)
+                .collect();
+            let Some((p, sz)) = fit.pick(n, &fits) else {
+                continue;
+            };
+            files[f].0 = p;
+            moves.push(Move {
+                id,
+                from: fpos,
+                to: p,
+                len: n,
+            });
+            spans.remove(&p);
+            if sz > n {
+                spans.insert(p + n, sz - n);
+                free.entry(sz - n).or_default().push(Reverse(p + n));
+            }
+            let (mut start, mut len) = (fpos, n);
+            if let Some((&lp, &ls)) = spans.range(..fpos).next_back() {
+                if lp + ls == fpos {
+                    start = lp;
+                    len += ls;
+                }
+            }
+            if let Some(rs) = spans.remove(&(fpos + n)) {
+                len += rs;
+            }
+            spans.insert(start, len);
+            free.entry(len).or_default().push(Reverse(start));
+        }
+
+        files.sort_unstable_by_key(|(pos, _, _)| *pos);
+        let mut out = Vec::with_capacity(files.len() * 2);
+        let mut pos = 0;
+        for (fpos, id, n) in files {
+            if fpos > pos {
+                out.push(Blocks::Empty(fpos - pos));
+            }
+            out.push(Blocks::File(id, n));
+            pos = fpos + n;
+        }
+        if disk_len > pos {
+            out.push(Blocks::Empty(disk_len - pos));
         }
+        self.0 = out;
+        moves
     }
 }
 
-fn part1(input: &[DiskMap]) -> usize {
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Fit {
+    First,
+    Best,
+    Worst,
+    MinFragments,
+}
+
+impl Fit {
+    fn pick(&self, n: u64, fits: &[(u64, u64)]) -> Option<(u64, u64)> {
+        let first = fits.iter().min().copied();
+        match self {
+            Fit::First => first,
+            Fit::Best => fits.first().copied(),
+            Fit::Worst => fits.last().copied(),
+            Fit::MinFragments => fits.iter().find(|(_, sz)| *sz == n).copied().or(first),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Move {
+    id: FileId,
+    from: u64,
+    to: u64,
+    len: u64,
+}
+
+trait DefragStrategy {
+    fn defrag(&self, dmap: &mut DiskMap) -> Vec<Move>;
+
+    fn blockwise(&self) -> bool {
+        false
+    }
+}
+
+struct BlockByBlock;
+
+impl DefragStrategy for BlockByBlock {
+    fn defrag(&self, dmap: &mut DiskMap) -> Vec<Move> {
+        dmap.compact()
+    }
+
+    fn blockwise(&self) -> bool {
+        true
+    }
+}
+
+impl DefragStrategy for Fit {
+    fn defrag(&self, dmap: &mut DiskMap) -> Vec<Move> {
+        dmap.move_files(*self)
+    }
+}
+
+#[allow(dead_code)]
+struct UntilStable<S>(S);
+
+impl<S: DefragStrategy> DefragStrategy for UntilStable<S> {
+    fn defrag(&self, dmap: &mut DiskMap) -> Vec<Move> {
+        let mut moves = Vec::new();
+        loop {
+            let pass = self.0.defrag(dmap);
+            if pass.is_empty() {
+                return moves;
+            }
+            moves.extend(pass);
+        }
+    }
+
+    fn blockwise(&self) -> bool {
+        self.0.blockwise()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct DefragMetrics {
+    fragmented_files: usize,
+    free_spans: usize,
+    largest_free_span: u64,
+    bytes_moved: u64,
+}
+
+fn part1(input: &[DiskMap]) -> u128 {
     let mut dmap = input[0].clone();
-    dmap.compact();
+    BlockByBlock.defrag(&mut dmap);
     dmap.checksum()
 }
 
-fn part2(input: &[DiskMap]) -> usize {
+fn part2(input: &[DiskMap]) -> u128 {
     let mut dmap = input[0].clone();
-    dmap.compact2();
+    Fit::First.defrag(&mut dmap);
     dmap.checksum()
 }
 
@@ -202,12 +587,247 @@ fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
     use ya_advent_lib::read::test_input;
 
+    const EXAMPLE: &str = "2333133121414131402";
+
+    fn layouts(trace: Vec<DiskMap>) -> Vec<String> {
+        trace.iter().map(|d| d.to_string()).collect()
+    }
+
     #[test]
     fn day09_test() {
         let input: Vec<DiskMap> = test_input("2333133121414131402");
         assert_eq!(part1(&input), 1928);
         assert_eq!(part2(&input), 2858);
     }
+
+    #[test]
+    fn day09_compact_test() {
+        let input: Vec<DiskMap> = test_input("12345");
+        assert_eq!(part1(&input), 60);
+        assert_eq!(part2(&input), 132);
+        let input: Vec<DiskMap> = test_input("1313165");
+        assert_eq!(part2(&input), 169);
+        let mut dmap = DiskMap(vec![Blocks::Empty(3), Blocks::File(0, 1)]);
+        assert_eq!(
+            BlockByBlock.defrag(&mut dmap),
+            vec![Move {
+                id: 0,
+                from: 3,
+                to: 0,
+                len: 1
+            }]
+        );
+        assert_eq!(dmap, DiskMap(vec![Blocks::File(0, 1)]));
+        let mut dmap = DiskMap(vec![
+            Blocks::Empty(2),
+            Blocks::File(0, 3),
+            Blocks::Empty(1),
+            Blocks::File(1, 1),
+        ]);
+        BlockByBlock.defrag(&mut dmap);
+        assert_eq!(
+            dmap,
+            DiskMap(vec![
+                Blocks::File(1, 1),
+                Blocks::File(0, 1),
+                Blocks::File(0, 2)
+            ])
+        );
+    }
+
+    #[test]
+    fn day09_checksum_test() {
+        let mut dmap = DiskMap(vec![
+            Blocks::File(0, 1),
+            Blocks::Empty(1_000_000_000),
+            Blocks::File(1, 1_000_000),
+        ]);
+        assert_eq!(dmap.checksum(), 1_000_500_000_500_000);
+        Fit::First.defrag(&mut dmap);
+        assert_eq!(dmap.checksum(), 500_000_500_000);
+        let dmap = DiskMap(vec![Blocks::File(1, 5_000_000_000)]);
+        assert_eq!(dmap.checksum(), 12_499_999_997_500_000_000);
+        let dmap = DiskMap(vec![
+            Blocks::Empty(u64::MAX / 2),
+            Blocks::File(1_000, 5_000_000_000),
+        ]);
+        assert_eq!(
+            dmap.checksum(),
+            1_000 * (5_000_000_000 * (u64::MAX / 2) as u128 + 12_499_999_997_500_000_000)
+        );
+    }
+
+    #[test]
+    fn day09_strategy_test() {
+        let input: Vec<DiskMap> = test_input(EXAMPLE);
+        for fit in [Fit::Best, Fit::Worst, Fit::MinFragments] {
+            let mut dmap = input[0].clone();
+            fit.defrag(&mut dmap);
+            assert_eq!(dmap.checksum(), 2858);
+        }
+        let input: Vec<DiskMap> = test_input("13122\n1213122");
+        for (fit, sums) in [
+            (Fit::First, [9, 20]),
+            (Fit::Best, [23, 20]),
+            (Fit::Worst, [9, 31]),
+            (Fit::MinFragments, [23, 20]),
+        ] {
+            for (dmap, sum) in input.iter().zip(sums) {
+                let mut dmap = dmap.clone();
+                fit.defrag(&mut dmap);
+                assert_eq!(dmap.checksum(), sum);
+            }
+        }
+    }
+
+    #[test]
+    fn day09_metrics_test() {
+        let input: Vec<DiskMap> = test_input(EXAMPLE);
+        let mut dmap = input[0].clone();
+        assert_eq!(
+            dmap.defrag(&Fit::First),
+            DefragMetrics {
+                fragmented_files: 0,
+                free_spans: 6,
+                largest_free_span: 5,
+                bytes_moved: 8,
+            }
+        );
+        let mut dmap = input[0].clone();
+        let metrics = dmap.defrag(&BlockByBlock);
+        assert_eq!(metrics.fragmented_files, 2);
+        assert_eq!(metrics.free_spans, 1);
+        assert_eq!(metrics.largest_free_span, 14);
+        let mut dmap = DiskMap(vec![
+            Blocks::File(0, 1),
+            Blocks::Empty(1),
+            Blocks::File(1, 1),
+            Blocks::Empty(4),
+        ]);
+        let metrics = dmap.defrag(&Fit::First);
+        assert_eq!(metrics.free_spans, 1);
+        assert_eq!(metrics.largest_free_span, 5);
+        let dmap = DiskMap(vec![Blocks::Empty(3)]);
+        let metrics = dmap.metrics(&dmap);
+        assert_eq!((metrics.free_spans, metrics.largest_free_span), (1, 3));
+        assert_eq!(
+            dmap.clone().defrag(&UntilStable(BlockByBlock)).bytes_moved,
+            0
+        );
+    }
+
+    #[test]
+    fn day09_render_test() {
+        let input: Vec<DiskMap> = test_input(EXAMPLE);
+        assert_eq!(
+            input[0].to_string(),
+            "00...111...2...333.44.5555.6666.777.888899"
+        );
+        assert_eq!(
+            format!("{:.3}", input[0]),
+            "00...111...2...333.44.5{4}.6{4}.777.8{4}99"
+        );
+    }
+
+    #[test]
+    fn day09_trace_test() {
+        let input: Vec<DiskMap> = test_input(EXAMPLE);
+        assert_eq!(
+            layouts(input[0].trace(&Fit::First)),
+            vec![
+                "00...111...2...333.44.5555.6666.777.888899",
+                "0099.111...2...333.44.5555.6666.777.8888..",
+                "0099.1117772...333.44.5555.6666.....8888..",
+                "0099.111777244.333....5555.6666.....8888..",
+                "00992111777.44.333....5555.6666.....8888..",
+            ]
+        );
+        assert_eq!(
+            layouts(input[0].trace(&BlockByBlock)),
+            vec![
+                "00...111...2...333.44.5555.6666.777.888899",
+                "009..111...2...333.44.5555.6666.777.88889.",
+                "0099.111...2...333.44.5555.6666.777.8888..",
+                "00998111...2...333.44.5555.6666.777.888...",
+                "009981118..2...333.44.5555.6666.777.88....",
+                "0099811188.2...333.44.5555.6666.777.8.....",
+                "009981118882...333.44.5555.6666.777.......",
+                "0099811188827..333.44.5555.6666.77........",
+                "00998111888277.333.44.5555.6666.7.........",
+                "009981118882777333.44.5555.6666...........",
+                "009981118882777333644.5555.666............",
+                "00998111888277733364465555.66.............",
+                "0099811188827773336446555566..............",
+            ]
+        );
+        let dmap: DiskMap = "12345".parse().unwrap();
+        assert_eq!(
+            layouts(dmap.trace(&BlockByBlock)),
+            vec![
+                "0..111....22222",
+                "02.111....2222.",
+                "022111....222..",
+                "0221112...22...",
+                "02211122..2....",
+                "022111222......",
+            ]
+        );
+    }
+
+    #[test]
+    fn day09_dense_test() {
+        let input: Vec<DiskMap> = test_input(EXAMPLE);
+        assert_eq!(input[0].to_dense(), EXAMPLE);
+        assert_eq!(
+            "12a45".parse::<DiskMap>(),
+            Err(ParseError { pos: 2, found: 'a' })
+        );
+        assert_eq!(
+            "12 4".parse::<DiskMap>().unwrap_err().to_string(),
+            "expected digit at 3, found ' '"
+        );
+        let mut dmap = input[0].clone();
+        Fit::First.defrag(&mut dmap);
+        assert_eq!(dmap.to_dense(), "20201030312134414542");
+        let dmap = DiskMap(vec![
+            Blocks::Empty(3),
+            Blocks::File(4, 12),
+            Blocks::File(4, 1),
+            Blocks::Empty(2),
+            Blocks::Empty(10),
+            Blocks::File(2, 1),
+        ]);
+        assert_eq!(dmap.to_dense(), "039049031");
+    }
+
+    #[test]
+    fn day09_roundtrip_test() {
+        let occupancy = |d: &DiskMap| d.to_string().replace(|c: char| c != '.', "#");
+        let mut rng = StdRng::seed_from_u64(9);
+        for _ in 0..500 {
+            let len = rng.gen_range(1..30);
+            let dense: String = (0..len)
+                .map(|_| char::from_digit(rng.gen_range(0..10), 10).unwrap())
+                .collect();
+            let dmap: DiskMap = dense.parse().unwrap();
+            let round: DiskMap = dmap.to_dense().parse().unwrap();
+            assert_eq!(round, dmap, "{dense}");
+            assert_eq!(round.to_dense(), dmap.to_dense(), "{dense}");
+            for strategy in [Fit::First, Fit::Best, Fit::Worst] {
+                let mut packed = dmap.clone();
+                strategy.defrag(&mut packed);
+                let round: DiskMap = packed.to_dense().parse().unwrap();
+                assert_eq!(occupancy(&round), occupancy(&packed), "{dense}");
+                assert_eq!(round.to_dense(), packed.to_dense(), "{dense}");
+            }
+            let mut packed = dmap.clone();
+            BlockByBlock.defrag(&mut packed);
+            let round: DiskMap = packed.to_dense().parse().unwrap();
+            assert_eq!(occupancy(&round), occupancy(&packed), "{dense}");
+        }
+    }
 }